@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// The scanner must never panic and the tokens it emits must be well-formed.
+fuzz_target!(|input: &str| {
+    saphyr_parser_fuzz::check_scanner(input);
+});
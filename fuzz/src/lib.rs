@@ -0,0 +1,336 @@
+//! Invariants checked by the fuzz targets.
+//!
+//! Each check panics when an invariant does not hold, so that the fuzzer reports the input.
+
+use std::collections::HashSet;
+
+use saphyr_parser::{
+    scanner::Scanner, Event, MarkedEventReceiver, Marker, Parser, ParserOptions, ScanError, Span,
+    SpannedEventReceiver, StrInput, TokenKind, YamlVersion,
+};
+
+/// Events along with their spans, as [`Parser::load`] reports them.
+type Events<'input> = Vec<(Event<'input>, Span)>;
+
+/// Events along with their markers, as the parser returns them.
+type MarkedEvents<'input> = Vec<(Event<'input>, Marker)>;
+
+/// A receiver collecting the events it receives.
+#[derive(Default)]
+struct Collector<'input> {
+    events: Events<'input>,
+}
+
+impl<'input> SpannedEventReceiver<'input> for Collector<'input> {
+    fn on_event(&mut self, ev: Event<'input>, span: Span) {
+        self.events.push((ev, span));
+    }
+}
+
+/// A receiver collecting the events it receives, along with their markers.
+#[derive(Default)]
+struct MarkedCollector<'input> {
+    events: MarkedEvents<'input>,
+}
+
+impl<'input> MarkedEventReceiver<'input> for MarkedCollector<'input> {
+    fn on_event(&mut self, ev: Event<'input>, mark: Marker) {
+        self.events.push((ev, mark));
+    }
+}
+
+/// Collect the events of `parser` through its `Iterator` implementation, up to the first error.
+fn iterate<'input>(
+    parser: &mut Parser<'input, StrInput<'input>>,
+) -> (MarkedEvents<'input>, Option<ScanError>) {
+    let mut events = vec![];
+    for event in parser {
+        match event {
+            Ok(event) => events.push(event),
+            Err(e) => return (events, Some(e)),
+        }
+    }
+    (events, None)
+}
+
+/// Collect the events of `input` through [`Parser::load`].
+fn load(input: &str, options: ParserOptions) -> (Events<'_>, Option<ScanError>) {
+    let mut collector = Collector::default();
+    let error = Parser::new_from_str(input)
+        .options(options)
+        .load(&mut collector, true)
+        .err();
+    (collector.events, error)
+}
+
+/// Collect the events of `input` through [`Parser::load`], along with their markers.
+fn load_marked(input: &str, options: ParserOptions) -> (MarkedEvents<'_>, Option<ScanError>) {
+    let mut collector = MarkedCollector::default();
+    let error = Parser::new_from_str(input)
+        .options(options)
+        .load(&mut collector, true)
+        .err();
+    (collector.events, error)
+}
+
+/// Load `input` and check the invariants of the resulting events and spans.
+///
+/// Returns the events without their spans, along with the error, if any.
+fn check_loaded(input: &str, options: ParserOptions) -> (Vec<Event<'_>>, Option<ScanError>) {
+    let (events, error) = load(input, options);
+    check_spans(&events);
+    check_nesting(&events, error.is_none());
+    check_aliases(&events);
+    (events.into_iter().map(|(ev, _)| ev).collect(), error)
+}
+
+/// Parse `input` and check the invariants of the resulting events.
+///
+/// # Panics
+/// Panics if an invariant does not hold.
+pub fn check_parser(input: &str) {
+    let mut parser = Parser::new_from_str(input);
+    let (events, error) = iterate(&mut parser);
+    let (loaded, load_error) = check_loaded(input, ParserOptions::default());
+    assert!(
+        events.iter().map(|(ev, _)| ev).eq(&loaded),
+        "`load` and `Iterator` events differ"
+    );
+    assert_eq!(error, load_error, "`load` and `Iterator` errors differ");
+    let (marked, _) = load_marked(input, ParserOptions::default());
+    assert_eq!(events, marked, "`load` and `Iterator` markers differ");
+
+    // Comments are interleaved with the other events, which they must not change.
+    let options = ParserOptions::default().keep_comments(true);
+    let (with_comments, comment_error) = iterate(&mut Parser::new_from_str(input).options(options));
+    let without_comments: MarkedEvents = with_comments
+        .into_iter()
+        .filter(|(ev, _)| !matches!(ev, Event::Comment(..)))
+        .collect();
+    assert_eq!(events, without_comments, "keeping comments changed events");
+    assert_eq!(error, comment_error, "keeping comments changed the error");
+    check_loaded(input, options);
+
+    // YAML 1.1 scans some input differently, but the resulting events must be as well-formed.
+    check_loaded(
+        input,
+        ParserOptions::default().yaml_version(YamlVersion::V1_1),
+    );
+
+    // Lenient mode only accepts input which is otherwise rejected, and warns about it.
+    let options = ParserOptions::default().lenient(true);
+    let mut lenient_parser = Parser::new_from_str(input).options(options);
+    let (lenient, _) = iterate(&mut lenient_parser);
+    check_loaded(input, options);
+    if error.is_none() {
+        assert_eq!(events, lenient, "lenient mode changed valid input");
+        assert_eq!(
+            parser.warnings(),
+            lenient_parser.warnings(),
+            "lenient mode warned about valid input"
+        );
+    }
+
+    // When recovering from errors, the stream must be complete and well-formed.
+    let options = ParserOptions::default().recover_from_errors(true);
+    let (recovered, recover_error) = iterate(&mut Parser::new_from_str(input).options(options));
+    assert_eq!(recover_error, None, "error when recovering from errors");
+    if error.is_none() {
+        assert_eq!(
+            events, recovered,
+            "recovering from errors changed valid input"
+        );
+    }
+    let (loaded, load_error) = load(input, options);
+    check_spans(&loaded);
+    check_nesting(&loaded, true);
+    check_aliases(&loaded);
+    assert!(
+        recovered.iter().map(|(ev, _)| ev).eq(loaded.iter().map(|(ev, _)| ev)),
+        "`load` and `Iterator` events differ when recovering"
+    );
+    assert_eq!(
+        load_error, None,
+        "error when loading and recovering from errors"
+    );
+}
+
+/// Scan `input` and check the invariants of the resulting tokens.
+///
+/// # Panics
+/// Panics if an invariant does not hold.
+pub fn check_scanner(input: &str) {
+    let mut scanner = Scanner::new(StrInput::new(input));
+    let mut tokens = vec![];
+    for token in scanner.by_ref() {
+        tokens.push(token);
+    }
+    let error = scanner.get_error();
+
+    let mut previous = None;
+    for token in &tokens {
+        let span = token.0;
+        assert!(
+            span.start.index() <= span.end.index(),
+            "token ends before it starts: {token:?}"
+        );
+        if let Some(previous) = previous {
+            assert_marker_order(previous, span, &token.1.kind());
+        }
+        previous = Some(span);
+    }
+
+    let kinds: Vec<_> = tokens.iter().map(|token| token.1.kind()).collect();
+    if let Some(first) = kinds.first() {
+        assert_eq!(
+            *first,
+            TokenKind::StreamStart,
+            "stream does not start with StreamStart"
+        );
+    }
+    assert!(
+        kinds
+            .iter()
+            .skip(1)
+            .all(|kind| *kind != TokenKind::StreamStart),
+        "StreamStart in the middle of the stream"
+    );
+    if error.is_none() {
+        assert_eq!(
+            kinds.last(),
+            Some(&TokenKind::StreamEnd),
+            "stream does not end"
+        );
+        assert_eq!(
+            kinds
+                .iter()
+                .filter(|kind| **kind == TokenKind::StreamEnd)
+                .count(),
+            1,
+            "more than one StreamEnd"
+        );
+    }
+}
+
+/// Check that `span` does not start before `previous`.
+fn assert_marker_order(previous: Span, span: Span, what: &dyn std::fmt::Debug) {
+    assert!(
+        previous.start.index() <= span.start.index()
+            && previous.start.byte_offset() <= span.start.byte_offset(),
+        "{what:?} at {span:?} starts before {previous:?}"
+    );
+}
+
+/// Check that each span is well-formed and that spans do not go backwards.
+fn check_spans(events: &Events) {
+    let mut previous = None;
+    for (ev, span) in events {
+        assert!(
+            span.start.index() <= span.end.index()
+                && span.start.byte_offset() <= span.end.byte_offset(),
+            "{ev:?} ends before it starts: {span:?}"
+        );
+        if let Some(previous) = previous {
+            assert_marker_order(previous, *span, ev);
+        }
+        previous = Some(*span);
+    }
+}
+
+/// Check that start and end events nest correctly.
+///
+/// If `complete` is set, the stream must also end with `StreamEnd` and every collection and
+/// document must be closed.
+fn check_nesting(events: &Events, complete: bool) {
+    /// What an event opened, along with the number of nodes it contains so far.
+    #[derive(Debug, PartialEq)]
+    enum Open {
+        Stream,
+        Document(usize),
+        Sequence,
+        Mapping(usize),
+    }
+
+    let mut stack: Vec<Open> = vec![];
+    let mut ended = false;
+    for (ev, _) in events {
+        assert!(!ended, "{ev:?} after StreamEnd");
+        let is_node = matches!(
+            ev,
+            Event::Alias(..)
+                | Event::Scalar(..)
+                | Event::Invalid
+                | Event::SequenceStart(..)
+                | Event::MappingStart(..)
+        );
+        if is_node {
+            match stack.last_mut() {
+                Some(Open::Document(nodes)) => {
+                    assert_eq!(*nodes, 0, "more than one node in a document");
+                    *nodes += 1;
+                }
+                Some(Open::Mapping(nodes)) => *nodes += 1,
+                Some(Open::Sequence) => {}
+                top => panic!("{ev:?} outside of a document, in {top:?}"),
+            }
+        }
+        match ev {
+            Event::StreamStart => {
+                assert!(stack.is_empty(), "StreamStart in {stack:?}");
+                stack.push(Open::Stream);
+            }
+            Event::StreamEnd => {
+                assert_eq!(stack.pop(), Some(Open::Stream), "unbalanced StreamEnd");
+                ended = true;
+            }
+            Event::DocumentStart(..) => {
+                assert_eq!(stack.last(), Some(&Open::Stream), "nested DocumentStart");
+                stack.push(Open::Document(0));
+            }
+            Event::DocumentEnd(..) => {
+                assert_eq!(
+                    stack.pop(),
+                    Some(Open::Document(1)),
+                    "unbalanced DocumentEnd"
+                );
+            }
+            Event::SequenceStart(..) => stack.push(Open::Sequence),
+            Event::SequenceEnd => {
+                assert_eq!(stack.pop(), Some(Open::Sequence), "unbalanced SequenceEnd");
+            }
+            Event::MappingStart(..) => stack.push(Open::Mapping(0)),
+            Event::MappingEnd => match stack.pop() {
+                Some(Open::Mapping(nodes)) => {
+                    assert!(nodes % 2 == 0, "mapping with a key but no value");
+                }
+                top => panic!("unbalanced MappingEnd in {top:?}"),
+            },
+            Event::Nothing => panic!("the parser emitted `Event::Nothing`"),
+            Event::Alias(..) | Event::Scalar(..) | Event::Invalid | Event::Comment(..) => {}
+        }
+    }
+    if complete {
+        assert!(ended, "stream does not end with StreamEnd");
+    }
+}
+
+/// Check that every alias refers to an anchor defined earlier in the same document.
+fn check_aliases(events: &Events) {
+    let mut anchors = HashSet::new();
+    for (ev, _) in events {
+        match ev {
+            Event::DocumentStart(..) => anchors.clear(),
+            Event::Scalar(_, _, id, _)
+            | Event::SequenceStart(id, ..)
+            | Event::MappingStart(id, ..)
+                if *id != 0 =>
+            {
+                anchors.insert(*id);
+            }
+            Event::Alias(id) => {
+                assert!(anchors.contains(id), "alias to unknown anchor ID {id}");
+            }
+            _ => {}
+        }
+    }
+}
@@ -12,10 +12,13 @@ pub(crate) fn is_break(c: char) -> bool {
     c == '\n' || c == '\r'
 }
 
-/// Check whether the character is nil or a line break (`\0`, `\r`, `\n`).
+/// Check whether the character is a line break in YAML 1.1 only.
+///
+/// These are next line (`\u{85}`), line separator (`\u{2028}`) and paragraph separator
+/// (`\u{2029}`).
 #[inline]
-pub(crate) fn is_breakz(c: char) -> bool {
-    is_break(c) || is_z(c)
+pub(crate) fn is_yaml_1_1_break(c: char) -> bool {
+    c == '\u{85}' || c == '\u{2028}' || c == '\u{2029}'
 }
 
 /// Check whether the character is a whitespace (` ` or `\t`).
@@ -24,14 +27,6 @@ pub(crate) fn is_blank(c: char) -> bool {
     c == ' ' || c == '\t'
 }
 
-/// Check whether the character is nil, a linebreak or a whitespace.
-///
-/// `\0`, ` `, `\t`, `\n`, `\r`
-#[inline]
-pub(crate) fn is_blank_or_breakz(c: char) -> bool {
-    is_blank(c) || is_breakz(c)
-}
-
 /// Check whether the character is an ascii digit.
 #[inline]
 pub(crate) fn is_digit(c: char) -> bool {
@@ -0,0 +1,205 @@
+//! Human-readable rendering of errors.
+//!
+//! A [`Diagnostic`] displays a [`ScanError`] along with the line of the source it occurred on,
+//! in the style of `rustc`:
+//!
+//! ```text
+//! error: mapping values are not allowed in this context
+//!  --> values.yaml:1:7
+//!   |
+//! 1 | key: a: b
+//!   |       ^
+//!   = note: in the block mapping started at values.yaml:1:1
+//!   = note: in the document started at values.yaml:1:1
+//! ```
+
+use std::fmt;
+
+use crate::{
+    char_traits::{is_break, is_yaml_1_1_break},
+    scanner::{Marker, ScanError, Span},
+};
+
+/// ANSI escape sequence for the `error` label and the markers under the source.
+const RED: &str = "\x1B[1;31m";
+/// ANSI escape sequence for the location and the gutter.
+const BLUE: &str = "\x1B[1;34m";
+/// ANSI escape sequence for the message.
+const BOLD: &str = "\x1B[1m";
+/// ANSI escape sequence to reset styles.
+const RESET: &str = "\x1B[0m";
+
+/// A [`ScanError`] rendered with a snippet of the source it occurred in.
+///
+/// The source must be the text that was given to the parser. The snippet shows the line on which
+/// the error occurred, with a caret under the position of the error or, if [`Self::span`] is
+/// set, with the span underlined. It is followed by a note for each construct the error occurred
+/// in (see [`ScanError::context`]). The rendering has no trailing newline.
+///
+/// ```
+/// # use saphyr_parser::{Diagnostic, Parser};
+/// let source = "key: a: b";
+/// let error = Parser::new_from_str(source)
+///     .find_map(Result::err)
+///     .unwrap();
+/// assert_eq!(
+///     Diagnostic::new(&error, source).origin("values.yaml").to_string(),
+///     "error: mapping values are not allowed in this context
+///  --> values.yaml:1:7
+///   |
+/// 1 | key: a: b
+///   |       ^
+///   = note: in the block mapping started at values.yaml:1:1
+///   = note: in the document started at values.yaml:1:1"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct Diagnostic<'a> {
+    /// The error to render.
+    error: &'a ScanError,
+    /// The source text the error occurred in.
+    source: &'a str,
+    /// The name of the source, displayed before the position of the error.
+    origin: Option<&'a str>,
+    /// The span to underline, if any.
+    span: Option<Span>,
+    /// Whether to use ANSI colors.
+    colors: bool,
+}
+
+impl<'a> Diagnostic<'a> {
+    /// Create a diagnostic for `error`, which occurred when parsing `source`.
+    #[must_use]
+    pub fn new(error: &'a ScanError, source: &'a str) -> Self {
+        Diagnostic {
+            error,
+            source,
+            origin: None,
+            span: None,
+            colors: false,
+        }
+    }
+
+    /// Set the name of the source (e.g.: a file name), displayed before the position.
+    #[must_use]
+    pub fn origin(mut self, origin: &'a str) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Underline `span` rather than pointing at the position of the error.
+    ///
+    /// If the span covers several lines, only its first line is shown and underlined up to its
+    /// end.
+    #[must_use]
+    pub fn span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Whether to color the output with ANSI escape sequences.
+    #[must_use]
+    pub fn colors(mut self, value: bool) -> Self {
+        self.colors = value;
+        self
+    }
+
+    /// Return `style` if colors are enabled, an empty string otherwise.
+    fn style(&self, style: &'static str) -> &'static str {
+        if self.colors {
+            style
+        } else {
+            ""
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (red, blue, bold, reset) = (
+            self.style(RED),
+            self.style(BLUE),
+            self.style(BOLD),
+            self.style(RESET),
+        );
+        let (start, end) = match self.span {
+            Some(span) => (span.start, Some(span.end)),
+            None => (*self.error.marker(), None),
+        };
+        let line_number = start.line().max(1);
+        let text = source_line(self.source, start);
+        let width = line_number.to_string().len();
+
+        writeln!(f, "{red}error{reset}{bold}: {}{reset}", self.error.info())?;
+        write!(f, "{:width$}{blue}-->{reset} ", "")?;
+        if let Some(origin) = self.origin {
+            write!(f, "{origin}:")?;
+        }
+        writeln!(f, "{line_number}:{}", start.col() + 1)?;
+        writeln!(f, "{:width$} {blue}|{reset}", "")?;
+        write!(f, "{blue}{line_number} |{reset}")?;
+        if !text.is_empty() {
+            write!(f, " {text}")?;
+        }
+        writeln!(f)?;
+
+        // Pad with the same whitespace as the line so that tabs keep the markers aligned.
+        let padding: String = text
+            .chars()
+            .chain(std::iter::repeat(' '))
+            .take(start.col())
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let len = match end {
+            Some(end) if end.line() == start.line() => end.col().saturating_sub(start.col()),
+            Some(_) => text.chars().count().saturating_sub(start.col()),
+            None => 1,
+        };
+        let markers = "^".repeat(len.max(1));
+        write!(
+            f,
+            "{:width$} {blue}|{reset} {padding}{red}{markers}{reset}",
+            ""
+        )?;
+
+        for (context, start) in self.error.context() {
+            write!(
+                f,
+                "\n{:width$} {blue}={reset} {bold}note{reset}: in the {context} started at ",
+                ""
+            )?;
+            if let Some(origin) = self.origin {
+                write!(f, "{origin}:")?;
+            }
+            write!(f, "{}:{}", start.line(), start.col() + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Return the line of `source` that `mark` is on, without its line break.
+///
+/// The line starts [`Marker::col`] characters before the byte offset of `mark`. It ends at the
+/// first line break of YAML 1.1, which also breaks lines on `\u{85}`, `\u{2028}` and `\u{2029}`,
+/// unless the line holds one of those before `mark`, which shows that it is a YAML 1.2 line.
+fn source_line(source: &str, mark: Marker) -> &str {
+    let before = source.get(..mark.byte_offset()).unwrap_or(source);
+    let mut start = before.len();
+    let mut crossed_1_1_break = false;
+    for (i, c) in before.char_indices().rev().take(mark.col()) {
+        crossed_1_1_break |= is_yaml_1_1_break(c);
+        start = i;
+    }
+    let rest = &source[start..];
+    let end = if crossed_1_1_break {
+        rest.find(is_break)
+    } else {
+        rest.find(is_any_break)
+    };
+    &rest[..end.unwrap_or(rest.len())]
+}
+
+/// Check whether the character is a line break in any version of YAML.
+fn is_any_break(c: char) -> bool {
+    is_break(c) || is_yaml_1_1_break(c)
+}
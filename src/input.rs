@@ -0,0 +1,353 @@
+//! Sources of characters for the [`crate::scanner::Scanner`].
+//!
+//! The scanner reads its input one character at a time through the [`Input`] trait. Any
+//! `Iterator<Item = char>` is an [`Input`]. [`StrInput`] reads from a `&str` and allows scalars to
+//! borrow from it. [`ReaderInput`] allows reading from any [`std::io::Read`], decoding the bytes as
+//! they come in.
+
+use std::{fmt, io, str::Chars};
+
+use crate::scanner::TEncoding;
+
+/// The size of the byte buffer of a [`ReaderInput`].
+///
+/// This is the maximum number of bytes that are read from the underlying reader at once. It must
+/// be at least 4 so that any character fits in the buffer, whatever the encoding.
+const READER_BUFFER_LEN: usize = 8 * 1024;
+
+/// A source of characters for the scanner.
+///
+/// `'input` is the lifetime of the source text, for inputs which hold it in memory.
+pub trait Input<'input> {
+    /// Read the next character from the input.
+    ///
+    /// Returns `Ok(None)` once the end of the input has been reached.
+    ///
+    /// # Errors
+    /// Returns an [`InputError`] if the input could not be read or decoded. Once an error has
+    /// been returned, the scanner considers the input to be exhausted and stops reading from it.
+    fn next_char(&mut self) -> Result<Option<char>, InputError>;
+
+    /// Return the encoding of the input.
+    ///
+    /// This is called after the first character has been read, so that inputs which detect their
+    /// encoding from the first bytes can report it.
+    fn encoding(&self) -> TEncoding {
+        TEncoding::Utf8
+    }
+
+    /// Return the source text between the given UTF-8 byte offsets, if it is available.
+    ///
+    /// Inputs which hold their whole source text as a `&'input str` return it, so that the
+    /// scanner can borrow scalars from it instead of allocating. Other inputs return `None`.
+    fn slice(&self, _start: usize, _end: usize) -> Option<&'input str> {
+        None
+    }
+}
+
+impl<T: Iterator<Item = char>> Input<'_> for T {
+    #[inline]
+    fn next_char(&mut self) -> Result<Option<char>, InputError> {
+        Ok(self.next())
+    }
+}
+
+/// An [`Input`] reading from a `&str`.
+///
+/// Scalars which appear verbatim in the source are borrowed from it rather than copied.
+#[derive(Clone, Debug)]
+pub struct StrInput<'input> {
+    /// The whole source text.
+    source: &'input str,
+    /// The characters of `source` that have not been read yet.
+    chars: Chars<'input>,
+}
+
+impl<'input> StrInput<'input> {
+    /// Create a new [`StrInput`] reading from `source`.
+    #[must_use]
+    pub fn new(source: &'input str) -> Self {
+        StrInput {
+            source,
+            chars: source.chars(),
+        }
+    }
+}
+
+impl<'input> Input<'input> for StrInput<'input> {
+    #[inline]
+    fn next_char(&mut self) -> Result<Option<char>, InputError> {
+        Ok(self.chars.next())
+    }
+
+    #[inline]
+    fn slice(&self, start: usize, end: usize) -> Option<&'input str> {
+        self.source.get(start..end)
+    }
+}
+
+/// An error that occurred while reading characters from an [`Input`].
+#[derive(Debug)]
+pub enum InputError {
+    /// The underlying reader returned an error.
+    Io(io::Error),
+    /// The input contains a byte sequence that is not valid in its encoding.
+    InvalidSequence(TEncoding),
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::Io(e) => write!(formatter, "error while reading input: {e}"),
+            InputError::InvalidSequence(encoding) => {
+                write!(formatter, "invalid {encoding} sequence in input")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InputError::Io(e) => Some(e),
+            InputError::InvalidSequence(_) => None,
+        }
+    }
+}
+
+/// An [`Input`] reading from a [`std::io::Read`].
+///
+/// Bytes are pulled from the reader into a fixed-size buffer and decoded as the scanner requests
+/// characters. The whole input is never held in memory at once, which allows parsing documents
+/// larger than the available memory.
+///
+/// The encoding of the input (UTF-8, UTF-16 or UTF-32) is detected from its first bytes, as
+/// described in [section 5.2 of the YAML 1.2
+/// specification](https://yaml.org/spec/1.2.2/#52-character-encodings). Byte order marks are
+/// decoded as `U+FEFF` characters and are handled by the scanner.
+///
+/// There is no need to wrap the reader in a [`std::io::BufReader`], as buffering is already done
+/// by [`ReaderInput`].
+pub struct ReaderInput<R> {
+    /// The reader from which we pull bytes.
+    reader: R,
+    /// Bytes read from `reader` that have not yet been decoded.
+    buffer: Box<[u8]>,
+    /// The index of the first byte in `buffer` that has not been decoded.
+    start: usize,
+    /// The index past the last byte in `buffer` that was read from `reader`.
+    end: usize,
+    /// The encoding of the input, once detected.
+    encoding: Option<TEncoding>,
+    /// Whether we have returned an error or reached the end of the input.
+    ///
+    /// Once set, no more bytes are read from `reader`.
+    done: bool,
+}
+
+impl<R: io::Read> ReaderInput<R> {
+    /// Create a new [`ReaderInput`] pulling bytes from `reader`.
+    pub fn new(reader: R) -> Self {
+        ReaderInput {
+            reader,
+            buffer: vec![0; READER_BUFFER_LEN].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            encoding: None,
+            done: false,
+        }
+    }
+
+    /// Return the number of bytes that are buffered but not yet decoded.
+    #[inline]
+    fn available(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Read more bytes from the reader into the buffer.
+    ///
+    /// Bytes that have not yet been decoded are moved to the front of the buffer first.
+    ///
+    /// # Return
+    /// Returns `Ok(false)` if the reader has no more bytes to give.
+    fn fill(&mut self) -> Result<bool, io::Error> {
+        self.buffer.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+        loop {
+            match self.reader.read(&mut self.buffer[self.end..]) {
+                Ok(0) => return Ok(false),
+                Ok(n) => {
+                    self.end += n;
+                    return Ok(true);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Read from the reader until at least `count` bytes are buffered.
+    ///
+    /// # Return
+    /// Returns `Ok(false)` if the reader ended before `count` bytes could be buffered.
+    fn ensure(&mut self, count: usize) -> Result<bool, InputError> {
+        while self.available() < count {
+            if !self.fill().map_err(InputError::Io)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Detect the encoding of the input from its first bytes.
+    fn detect_encoding(&mut self) -> Result<TEncoding, InputError> {
+        self.ensure(4)?;
+        let bytes = &self.buffer[self.start..self.end];
+        // See the table in section 5.2 of the specification. The byte order mark is optional. If
+        // absent, the encoding is deduced from the null bytes surrounding the first character,
+        // which must be ASCII.
+        Ok(match *bytes {
+            [0, 0, 0xFE, 0xFF, ..] | [0, 0, 0, _, ..] => TEncoding::Utf32Be,
+            [0xFF, 0xFE, 0, 0, ..] | [_, 0, 0, 0, ..] => TEncoding::Utf32Le,
+            [0xFE, 0xFF, ..] | [0, _, ..] => TEncoding::Utf16Be,
+            [0xFF, 0xFE, ..] | [_, 0, ..] => TEncoding::Utf16Le,
+            _ => TEncoding::Utf8,
+        })
+    }
+
+    /// Decode the next character from the buffer, reading from the reader if needed.
+    fn decode_next(&mut self) -> Result<Option<char>, InputError> {
+        let encoding = if let Some(encoding) = self.encoding {
+            encoding
+        } else {
+            let encoding = self.detect_encoding()?;
+            self.encoding = Some(encoding);
+            encoding
+        };
+
+        if self.available() == 0 && !self.fill().map_err(InputError::Io)? {
+            return Ok(None);
+        }
+
+        match encoding {
+            TEncoding::Utf8 => self.decode_utf8(),
+            TEncoding::Utf16Le => self.decode_utf16(encoding, u16::from_le_bytes),
+            TEncoding::Utf16Be => self.decode_utf16(encoding, u16::from_be_bytes),
+            TEncoding::Utf32Le => self.decode_utf32(encoding, u32::from_le_bytes),
+            TEncoding::Utf32Be => self.decode_utf32(encoding, u32::from_be_bytes),
+        }
+    }
+
+    /// Decode a UTF-8 character from the buffer.
+    ///
+    /// The buffer must not be empty.
+    fn decode_utf8(&mut self) -> Result<Option<char>, InputError> {
+        let invalid = InputError::InvalidSequence(TEncoding::Utf8);
+        let lead = self.buffer[self.start];
+        if lead.is_ascii() {
+            self.start += 1;
+            return Ok(Some(char::from(lead)));
+        }
+
+        let width = match lead {
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return Err(invalid),
+        };
+        if !self.ensure(width)? {
+            // The input ends in the middle of a sequence.
+            return Err(invalid);
+        }
+
+        let bytes = &self.buffer[self.start..self.start + width];
+        let c = std::str::from_utf8(bytes)
+            .map_err(|_| invalid)?
+            .chars()
+            .next();
+        self.start += width;
+        Ok(c)
+    }
+
+    /// Decode a UTF-16 character from the buffer, using `from_bytes` to read code units.
+    ///
+    /// The buffer must not be empty.
+    fn decode_utf16(
+        &mut self,
+        encoding: TEncoding,
+        from_bytes: fn([u8; 2]) -> u16,
+    ) -> Result<Option<char>, InputError> {
+        let unit_at = |buffer: &[u8], i: usize| from_bytes([buffer[i], buffer[i + 1]]);
+        if !self.ensure(2)? {
+            return Err(InputError::InvalidSequence(encoding));
+        }
+
+        let high = unit_at(&self.buffer, self.start);
+        let c = match high {
+            0xD800..=0xDBFF => {
+                if !self.ensure(4)? {
+                    return Err(InputError::InvalidSequence(encoding));
+                }
+                let low = unit_at(&self.buffer, self.start + 2);
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(InputError::InvalidSequence(encoding));
+                }
+                self.start += 2;
+                0x10000 + ((u32::from(high) - 0xD800) << 10) + (u32::from(low) - 0xDC00)
+            }
+            0xDC00..=0xDFFF => return Err(InputError::InvalidSequence(encoding)),
+            _ => u32::from(high),
+        };
+        self.start += 2;
+        Ok(char::from_u32(c))
+    }
+
+    /// Decode a UTF-32 character from the buffer, using `from_bytes` to read code units.
+    ///
+    /// The buffer must not be empty.
+    fn decode_utf32(
+        &mut self,
+        encoding: TEncoding,
+        from_bytes: fn([u8; 4]) -> u32,
+    ) -> Result<Option<char>, InputError> {
+        if !self.ensure(4)? {
+            return Err(InputError::InvalidSequence(encoding));
+        }
+
+        let b = &self.buffer[self.start..self.start + 4];
+        let Some(c) = char::from_u32(from_bytes([b[0], b[1], b[2], b[3]])) else {
+            return Err(InputError::InvalidSequence(encoding));
+        };
+        self.start += 4;
+        Ok(Some(c))
+    }
+}
+
+impl<R: io::Read> Input<'_> for ReaderInput<R> {
+    fn next_char(&mut self) -> Result<Option<char>, InputError> {
+        if self.done {
+            return Ok(None);
+        }
+        let result = self.decode_next();
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result
+    }
+
+    fn encoding(&self) -> TEncoding {
+        self.encoding.unwrap_or(TEncoding::Utf8)
+    }
+}
+
+impl<R> fmt::Debug for ReaderInput<R> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("ReaderInput")
+            .field("buffered", &(self.end - self.start))
+            .field("encoding", &self.encoding)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
@@ -21,22 +21,33 @@
 //! ```
 //!
 //! # Features
-//! **Note:** With all features disabled, this crate's MSRV is `1.65.0`.
+//! **Note:** This crate's MSRV is `1.70.0`.
 //!
 //! #### `debug_prints`
 //! Enables the `debug` module and usage of debug prints in the scanner and the parser. Do not
 //! enable if you are consuming the crate rather than working on it as this can significantly
 //! decrease performance.
-//!
-//! The MSRV for this feature is `1.70.0`.
 
 #![warn(missing_docs, clippy::pedantic)]
 
 pub(crate) mod char_traits;
 #[macro_use]
 pub(crate) mod debug;
+pub mod diagnostic;
+pub mod input;
+pub mod limits;
+pub mod options;
 pub mod parser;
 pub mod scanner;
 
-pub use crate::parser::{Event, EventReceiver, MarkedEventReceiver, Parser, Tag};
-pub use crate::scanner::{Marker, ScanError, TScalarStyle};
+pub use crate::diagnostic::Diagnostic;
+pub use crate::input::{Input, ReaderInput, StrInput};
+pub use crate::limits::Limits;
+pub use crate::options::{ParserOptions, YamlVersion};
+pub use crate::parser::{
+    CollectionStyle, Event, EventReceiver, MarkedEventReceiver, Parser, SpannedEventReceiver, Tag,
+};
+pub use crate::scanner::{
+    BlockScalarHeader, Chomping, CommentStyle, ErrorContext, Marker, ScanError, ScanErrorKind,
+    Span, TScalarStyle, TokenKind, Warning, WarningKind,
+};
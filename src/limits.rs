@@ -0,0 +1,131 @@
+//! Limits on the resources used to parse untrusted input.
+
+/// Limits on the resources used to parse a YAML stream.
+///
+/// Exceeding a limit is an error, each with its own [`ScanErrorKind`], which stops parsing, even
+/// when [recovering from errors](crate::ParserOptions::recover_from_errors). All limits default to
+/// being unbounded, apart from the nesting of flow collections which the scanner caps at 255.
+///
+/// ```
+/// # use saphyr_parser::{Limits, Parser, ParserOptions, ScanErrorKind};
+/// let limits = Limits::default().max_depth(2);
+/// let error = Parser::new_from_str("a: [b, [c]]")
+///     .options(ParserOptions::default().limits(limits))
+///     .find_map(Result::err)
+///     .unwrap();
+/// assert_eq!(error.kind(), &ScanErrorKind::DepthLimitExceeded);
+/// ```
+///
+/// [`ScanErrorKind`]: crate::ScanErrorKind
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+#[allow(clippy::struct_field_names)]
+pub struct Limits {
+    /// See [`Self::max_depth`].
+    pub(crate) max_depth: usize,
+    /// See [`Self::max_scalar_bytes`].
+    pub(crate) max_scalar_bytes: usize,
+    /// See [`Self::max_events`].
+    pub(crate) max_events: usize,
+    /// See [`Self::max_documents`].
+    pub(crate) max_documents: usize,
+    /// See [`Self::max_anchors`].
+    pub(crate) max_anchors: usize,
+    /// See [`Self::max_aliases`].
+    pub(crate) max_aliases: usize,
+    /// See [`Self::max_expanded_nodes`].
+    pub(crate) max_expanded_nodes: usize,
+    /// See [`Self::max_expansion_ratio`].
+    pub(crate) max_expansion_ratio: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: usize::MAX,
+            max_scalar_bytes: usize::MAX,
+            max_events: usize::MAX,
+            max_documents: usize::MAX,
+            max_anchors: usize::MAX,
+            max_aliases: usize::MAX,
+            max_expanded_nodes: usize::MAX,
+            max_expansion_ratio: usize::MAX,
+        }
+    }
+}
+
+impl Limits {
+    /// Set the maximum number of nested collections, block and flow alike.
+    ///
+    /// A collection at the root of a document has a depth of 1.
+    #[must_use]
+    pub fn max_depth(mut self, value: usize) -> Self {
+        self.max_depth = value;
+        self
+    }
+
+    /// Set the maximum length of the contents of a scalar, in bytes once encoded in UTF-8.
+    ///
+    /// This is enforced by the scanner as it reads each character, so that no more than this is
+    /// buffered for a single token, even when reading from a [`std::io::Read`]. Whitespace and
+    /// line breaks count toward the limit as soon as they are read. The limit also applies to
+    /// kept comments, anchor and alias names, tags and directive names.
+    #[must_use]
+    pub fn max_scalar_bytes(mut self, value: usize) -> Self {
+        self.max_scalar_bytes = value;
+        self
+    }
+
+    /// Set the maximum number of events in the stream, comments excluded.
+    #[must_use]
+    pub fn max_events(mut self, value: usize) -> Self {
+        self.max_events = value;
+        self
+    }
+
+    /// Set the maximum number of documents in the stream.
+    #[must_use]
+    pub fn max_documents(mut self, value: usize) -> Self {
+        self.max_documents = value;
+        self
+    }
+
+    /// Set the maximum number of anchors defined in the stream.
+    ///
+    /// The limit covers the whole stream rather than each document, as the names of the anchors
+    /// of all documents are kept for [`Parser::anchor_name`](crate::Parser::anchor_name). Anchors
+    /// which redefine an earlier one count as well.
+    #[must_use]
+    pub fn max_anchors(mut self, value: usize) -> Self {
+        self.max_anchors = value;
+        self
+    }
+
+    /// Set the maximum number of aliases in the stream.
+    #[must_use]
+    pub fn max_aliases(mut self, value: usize) -> Self {
+        self.max_aliases = value;
+        self
+    }
+
+    /// Set the maximum number of nodes in the stream once aliases are expanded.
+    ///
+    /// Each alias counts for as many nodes as the node it refers to, aliases within included, so
+    /// that nested aliases (the "billion laughs" attack) are caught before a loader expands them.
+    #[must_use]
+    pub fn max_expanded_nodes(mut self, value: usize) -> Self {
+        self.max_expanded_nodes = value;
+        self
+    }
+
+    /// Set the maximum ratio of the number of nodes in the stream once aliases are expanded to
+    /// the number of nodes in the stream.
+    ///
+    /// Unlike [`Self::max_expanded_nodes`], this allows larger inputs to expand to more nodes.
+    /// Note that small inputs which reuse an anchored collection a few times may legitimately have
+    /// a high ratio.
+    #[must_use]
+    pub fn max_expansion_ratio(mut self, value: usize) -> Self {
+        self.max_expansion_ratio = value;
+        self
+    }
+}
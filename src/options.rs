@@ -0,0 +1,179 @@
+//! Options to configure a [`Parser`](crate::Parser) and its [`Scanner`](crate::scanner::Scanner).
+
+use crate::limits::Limits;
+
+/// Options for a [`Parser`] and the [`Scanner`] underneath it.
+///
+/// Options are set on any parser, whatever its input, with [`Parser::options`], which also
+/// configures its scanner. When set with [`Scanner::options`], the options which only apply to the
+/// parser ([`Self::keep_tags`] and [`Self::recover_from_errors`]) are ignored.
+///
+/// Whether [`Parser::load`] loads one or all documents of the stream is chosen on each call.
+///
+/// ```
+/// # use saphyr_parser::{CommentStyle, Event, Limits, Parser, ParserOptions};
+/// let options = ParserOptions::default()
+///     .keep_comments(true)
+///     .limits(Limits::default().max_depth(64));
+/// let events: Vec<_> = Parser::new_from_str("a: b # c")
+///     .options(options)
+///     .map(|event| event.unwrap().0)
+///     .collect();
+/// assert!(events.contains(&Event::Comment(" c".into(), CommentStyle::Trailing)));
+/// ```
+///
+/// [`Parser`]: crate::Parser
+/// [`Parser::options`]: crate::Parser::options
+/// [`Parser::load`]: crate::Parser::load
+/// [`Scanner`]: crate::scanner::Scanner
+/// [`Scanner::options`]: crate::scanner::Scanner::options
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ParserOptions {
+    /// See [`Self::keep_tags`].
+    pub(crate) keep_tags: bool,
+    /// See [`Self::keep_comments`].
+    pub(crate) keep_comments: bool,
+    /// See [`Self::recover_from_errors`].
+    pub(crate) recover_from_errors: bool,
+    /// See [`Self::limits`].
+    pub(crate) limits: Limits,
+    /// See [`Self::yaml_version`].
+    pub(crate) yaml_version: YamlVersion,
+    /// See [`Self::lenient`].
+    pub(crate) lenient: bool,
+}
+
+impl ParserOptions {
+    /// Whether to keep tags across multiple documents when parsing.
+    ///
+    /// This behavior is non-standard as per the YAML specification but can be encountered in the
+    /// wild. This boolean allows enabling this non-standard extension. This would result in the
+    /// parser accepting input from [test
+    /// QLJ7](https://github.com/yaml/yaml-test-suite/blob/ccfa74e56afb53da960847ff6e6976c0a0825709/src/QLJ7.yaml)
+    /// of the yaml-test-suite:
+    ///
+    /// ```yaml
+    /// %TAG !prefix! tag:example.com,2011:
+    /// --- !prefix!A
+    /// a: b
+    /// --- !prefix!B
+    /// c: d
+    /// --- !prefix!C
+    /// e: f
+    /// ```
+    ///
+    /// With `keep_tags` set to `false`, the above YAML is rejected. As per the specification, tags
+    /// only apply to the document immediately following them. This would error on `!prefix!B`.
+    ///
+    /// With `keep_tags` set to `true`, the above YAML is accepted by the parser.
+    #[must_use]
+    pub fn keep_tags(mut self, value: bool) -> Self {
+        self.keep_tags = value;
+        self
+    }
+
+    /// Whether to emit [`Event::Comment`](crate::Event::Comment) events.
+    ///
+    /// Comments are emitted right before the first event which starts after them in the source.
+    /// Events of other kinds are the same regardless of this setting. The scanner records
+    /// comments, which are retrieved with
+    /// [`Scanner::pop_comment_before`](crate::scanner::Scanner::pop_comment_before).
+    #[must_use]
+    pub fn keep_comments(mut self, value: bool) -> Self {
+        self.keep_comments = value;
+        self
+    }
+
+    /// Whether to recover from errors and keep parsing.
+    ///
+    /// When set, errors are collected into [`Parser::errors`](crate::Parser::errors) rather than
+    /// returned, and parsing resumes on the next line. Flow collections in which an error occurs
+    /// are skipped up to their closing bracket, or up to a line indented at or below the block
+    /// collection they are in, and closed.
+    /// [`Event::Invalid`](crate::Event::Invalid) placeholders stand for nodes which could not be
+    /// parsed, so that every mapping key still has a value. Block collections are resumed on the
+    /// next line that is indented enough, as usual.
+    ///
+    /// The emitted events are thus always well-formed, but only a best-effort interpretation of
+    /// invalid input.
+    #[must_use]
+    pub fn recover_from_errors(mut self, value: bool) -> Self {
+        self.recover_from_errors = value;
+        self
+    }
+
+    /// Set the limits on the resources used to parse the input.
+    ///
+    /// The scanner only enforces [`Limits::max_scalar_bytes`]. See [`Limits`].
+    #[must_use]
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the version of YAML by which to parse documents without a `%YAML` directive.
+    ///
+    /// Documents with a `%YAML` directive are parsed by the version it names, see
+    /// [`YamlVersion::from_directive`]. Defaults to [`YamlVersion::V1_2`].
+    #[must_use]
+    pub fn yaml_version(mut self, version: YamlVersion) -> Self {
+        self.yaml_version = version;
+        self
+    }
+
+    /// Whether to accept common mistakes which the specification rejects.
+    ///
+    /// When set, the following are accepted with their obvious meaning rather than rejected:
+    ///   - tabs in the indentation of block collections and of the continuation lines of
+    ///     scalars, where each tab counts as a single column;
+    ///   - comments which are not separated by whitespace from the preceding token, such as
+    ///     `"a"#comment`;
+    ///   - plain scalars starting with the reserved indicators `@` and `` ` ``.
+    ///
+    /// Each of them is reported as a [`Warning`](crate::Warning) instead, see
+    /// [`Parser::warnings`](crate::Parser::warnings).
+    #[must_use]
+    pub fn lenient(mut self, value: bool) -> Self {
+        self.lenient = value;
+        self
+    }
+}
+
+/// A version of the YAML specification by which a document is parsed.
+///
+/// The versions differ in how they scan some input:
+///   - YAML 1.1 also treats next line (`\u{85}`), line separator (`\u{2028}`) and paragraph
+///     separator (`\u{2029}`) characters as line breaks. In scalars, next lines are normalized to
+///     `\n` while line and paragraph separators are kept as is and never folded.
+///   - YAML 1.1 has no `\/` escape sequence in double-quoted scalars.
+///
+/// Other differences, such as which plain scalars are booleans, belong to the schema. The parser
+/// leaves them to its consumer, which finds the version of each document in
+/// [`Event::DocumentStart`](crate::Event::DocumentStart).
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Default)]
+pub enum YamlVersion {
+    /// YAML 1.1, also used for `%YAML 1.0` documents.
+    V1_1,
+    /// YAML 1.2, also used for documents with a later `%YAML` version.
+    #[default]
+    V1_2,
+}
+
+impl YamlVersion {
+    /// Return the version by which to parse a document with a `%YAML major.minor` directive.
+    ///
+    /// ```
+    /// # use saphyr_parser::YamlVersion;
+    /// assert_eq!(YamlVersion::from_directive(1, 1), YamlVersion::V1_1);
+    /// assert_eq!(YamlVersion::from_directive(1, 3), YamlVersion::V1_2);
+    /// ```
+    #[must_use]
+    pub fn from_directive(major: u32, minor: u32) -> YamlVersion {
+        if major == 1 && minor < 2 {
+            YamlVersion::V1_1
+        } else {
+            YamlVersion::V1_2
+        }
+    }
+}
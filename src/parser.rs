@@ -4,8 +4,18 @@
 //! compliance, and emits a stream of YAML events. This stream can for instance be used to create
 //! YAML objects.
 
-use crate::scanner::{Marker, ScanError, Scanner, TScalarStyle, Token, TokenType};
-use std::collections::HashMap;
+use crate::{
+    input::{Input, ReaderInput, StrInput},
+    options::{ParserOptions, YamlVersion},
+    scanner::{
+        CommentStyle, ErrorContext, Marker, ScanError, ScanErrorKind, Scanner, Span, TScalarStyle,
+        Token, TokenKind, TokenType, Warning, WarningKind,
+    },
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+};
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 enum State {
@@ -37,31 +47,60 @@ enum State {
 ///
 /// Events are used in the low-level event-based API (push parser). The API entrypoint is the
 /// [`EventReceiver`] trait.
+///
+/// `'input` is the lifetime of the source text. When parsing from a `&str`, scalars which appear
+/// verbatim in the source borrow from it.
 #[derive(Clone, PartialEq, Debug, Eq)]
-pub enum Event {
+pub enum Event<'input> {
     /// Reserved for internal use.
     Nothing,
     /// Event generated at the very beginning of parsing.
     StreamStart,
     /// Last event that will be generated by the parser. Signals EOF.
     StreamEnd,
-    /// The YAML start document directive (`---`).
-    DocumentStart,
-    /// The YAML end document directive (`...`).
-    DocumentEnd,
+    /// The start of a YAML document.
+    DocumentStart(
+        /// Whether the document starts with an explicit `---` marker.
+        bool,
+        /// The `%YAML` directive of the document, as `(major, minor)`, if any.
+        Option<(u32, u32)>,
+        /// The `%TAG` directives of the document, as `(handle, prefix)`, in source order.
+        Vec<(String, String)>,
+        /// The `%TAG` directives of previous documents still in effect, as `(handle, prefix)`,
+        /// sorted by handle.
+        ///
+        /// This is only ever non-empty with [`ParserOptions::keep_tags`]. Handles declared again
+        /// by the document are not included. Together with the directives of the document, these
+        /// are the tag directives in effect.
+        Vec<(String, String)>,
+        /// The version of YAML by which the document is parsed, named by its `%YAML` directive or
+        /// else set with [`ParserOptions::yaml_version`].
+        YamlVersion,
+    ),
+    /// The end of a YAML document.
+    DocumentEnd(
+        /// Whether the document ends with an explicit `...` marker.
+        bool,
+    ),
     /// A YAML Alias.
     Alias(
         /// The anchor ID the alias refers to.
         usize,
     ),
     /// Value, style, `anchor_id`, tag
-    Scalar(String, TScalarStyle, usize, Option<Tag>),
+    ///
+    /// When the content of a node is omitted (e.g.: `key:`), the parser emits a plain scalar
+    /// whose value is `~`, or empty if the node has a tag. Such scalars can be told apart from an
+    /// explicit `~` by their empty [`Span`].
+    Scalar(Cow<'input, str>, TScalarStyle, usize, Option<Tag>),
     /// The start of a YAML sequence (array).
     SequenceStart(
         /// The anchor ID of the start of the sequence.
         usize,
         /// An optional tag
         Option<Tag>,
+        /// How the sequence is written in the source.
+        CollectionStyle,
     ),
     /// The end of a YAML sequence (array).
     SequenceEnd,
@@ -71,9 +110,107 @@ pub enum Event {
         usize,
         /// An optional tag
         Option<Tag>,
+        /// How the mapping is written in the source.
+        CollectionStyle,
     ),
     /// The end of a YAML mapping (object, hash).
     MappingEnd,
+    /// A comment, only emitted if [`ParserOptions::keep_comments`] is set.
+    Comment(
+        /// The text of the comment, without the leading `#`.
+        Cow<'input, str>,
+        /// Whether the comment is alone on its line.
+        CommentStyle,
+    ),
+    /// A placeholder for a node that could not be parsed.
+    ///
+    /// Only emitted if [`ParserOptions::recover_from_errors`] is set. The error itself is available from
+    /// [`Parser::errors`].
+    Invalid,
+}
+
+/// The style in which a collection is written in the source.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum CollectionStyle {
+    /// A block collection, with one entry or key per line.
+    Block,
+    /// A block sequence whose entries are not indented relative to the mapping key it is the
+    /// value of.
+    ///
+    /// ```yaml
+    /// key:
+    /// - a
+    /// - b
+    /// ```
+    Indentless,
+    /// A flow collection, enclosed in `[]` or `{}`.
+    Flow,
+    /// A single-pair mapping inside a flow sequence, written without braces (`[a: b]` or
+    /// `[? a : b]`).
+    FlowPair,
+}
+
+/// A document or collection the parser is in, tracked to give context to errors and recover from
+/// them.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+enum Container {
+    Document,
+    Sequence(CollectionStyle),
+    Mapping(CollectionStyle),
+}
+
+/// A [`Container`] along with where it started and the number of nodes it contains so far.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+struct OpenContainer {
+    container: Container,
+    /// The start of the event which opened the container.
+    start: Marker,
+    /// The ID of the anchor of the collection, 0 if none.
+    anchor: usize,
+    /// The number of complete nodes in the container, keys and values included.
+    nodes: usize,
+    /// The number of nodes in the collection once aliases are expanded, itself included.
+    expanded: usize,
+}
+
+impl OpenContainer {
+    /// The [`ErrorContext`] of errors happening in the container.
+    fn error_context(self) -> ErrorContext {
+        match self.container {
+            Container::Document => ErrorContext::Document,
+            Container::Sequence(CollectionStyle::Flow) => ErrorContext::FlowSequence,
+            Container::Sequence(_) => ErrorContext::BlockSequence,
+            Container::Mapping(CollectionStyle::Block) => ErrorContext::BlockMapping,
+            Container::Mapping(_) => ErrorContext::FlowMapping,
+        }
+    }
+
+    /// Whether the container is a flow collection.
+    fn is_flow(self) -> bool {
+        matches!(
+            self.container,
+            Container::Sequence(CollectionStyle::Flow | CollectionStyle::FlowPair)
+                | Container::Mapping(CollectionStyle::Flow | CollectionStyle::FlowPair)
+        )
+    }
+
+    /// Whether the container is a mapping which has a key without a value.
+    fn lacks_value(self) -> bool {
+        matches!(self.container, Container::Mapping(_)) && self.nodes % 2 == 1
+    }
+
+    /// The state in which to resume parsing the container, once its current node is complete.
+    ///
+    /// Flow collections are never resumed and must have been closed.
+    fn resume_state(self) -> State {
+        match self.container {
+            Container::Document => State::DocumentEnd,
+            Container::Sequence(CollectionStyle::Indentless) => State::IndentlessSequenceEntry,
+            Container::Sequence(_) => State::BlockSequenceEntry,
+            Container::Mapping(_) if self.nodes % 2 == 0 => State::BlockMappingValue,
+            Container::Mapping(_) => State::BlockMappingKey,
+        }
+    }
 }
 
 /// A YAML tag.
@@ -85,24 +222,28 @@ pub struct Tag {
     pub suffix: String,
 }
 
-impl Event {
+impl Event<'_> {
     /// Create an empty scalar.
-    fn empty_scalar() -> Event {
-        // a null scalar
-        Event::Scalar("~".to_owned(), TScalarStyle::Plain, 0, None)
+    fn empty_scalar() -> Self {
+        Event::empty_scalar_with_anchor(0, None)
     }
 
     /// Create an empty scalar with the given anchor.
-    fn empty_scalar_with_anchor(anchor: usize, tag: Option<Tag>) -> Event {
-        Event::Scalar(String::new(), TScalarStyle::Plain, anchor, tag)
+    ///
+    /// Without a tag, the value is `~` so that it resolves to null. With a tag, the tag is applied
+    /// to the empty content (e.g.: `!!str` yields an empty string).
+    fn empty_scalar_with_anchor(anchor: usize, tag: Option<Tag>) -> Self {
+        let value = if tag.is_some() { "" } else { "~" };
+        Event::Scalar(value.into(), TScalarStyle::Plain, anchor, tag)
     }
 }
 
 /// A YAML parser.
 #[derive(Debug)]
-pub struct Parser<T> {
+#[allow(clippy::struct_excessive_bools)]
+pub struct Parser<'input, T> {
     /// The underlying scanner from which we pull tokens.
-    scanner: Scanner<T>,
+    scanner: Scanner<'input, T>,
     /// The stack of _previous_ states we were in.
     ///
     /// States are pushed in the context of subobjects to this stack. The top-most element is the
@@ -111,16 +252,23 @@ pub struct Parser<T> {
     /// The state in which we currently are.
     state: State,
     /// The next token from the scanner.
-    token: Option<Token>,
+    token: Option<Token<'input>>,
     /// The next YAML event to emit.
-    current: Option<(Event, Marker)>,
+    current: Option<MarkedSpannedEvent<'input>>,
+    /// An event that has been parsed, but which comes after comments that have yet to be emitted.
+    delayed: Option<MarkedSpannedEvent<'input>>,
     /// Anchors that have been encountered in the YAML document.
-    anchors: HashMap<String, usize>,
+    anchors: HashMap<Cow<'input, str>, usize>,
     /// Next ID available for an anchor.
     ///
     /// Every anchor is given a unique ID. We use an incrementing ID and this is both the ID to
     /// return for the next anchor and the count of anchor IDs emitted.
     anchor_id_count: usize,
+    /// The names of all anchors that have been encountered, indexed by their ID minus 1.
+    ///
+    /// Unlike [`Self::anchors`], this is not cleared between documents, so that names can be
+    /// looked up for any ID that was emitted.
+    anchor_names: Vec<Cow<'input, str>>,
     /// The tag directives (`%TAG`) the parser has encountered.
     ///
     /// Key is the handle, and value is the prefix.
@@ -130,8 +278,44 @@ pub struct Parser<T> {
     /// Emitted means that it has been returned from [`Self::next_token`]. If it is stored in
     /// [`Self::token`], this is set to `false`.
     stream_end_emitted: bool,
-    /// Make tags global across all documents.
-    keep_tags: bool,
+    /// The end of the last non-empty token we consumed from the scanner.
+    ///
+    /// Events which are not backed by any character of the input (empty scalars, the end of
+    /// block collections, ...) are given an empty span at this position.
+    last_token_end: Marker,
+    /// The furthest end of the events emitted so far.
+    ///
+    /// Events generated when recovering from an error must not be placed before it.
+    last_event_end: Marker,
+    /// The marker [`Self::next_event`] returns for the event being parsed, if it is not the marker
+    /// of the last token we peeked at (see [`Scanner::token_marker`]).
+    marker: Option<Marker>,
+    /// The errors encountered while recovering from errors.
+    errors: Vec<ScanError>,
+    /// Events generated when recovering from an error, to be emitted before parsing resumes.
+    recovered: VecDeque<(Event<'input>, Span)>,
+    /// The documents and collections we are in.
+    open: Vec<OpenContainer>,
+    /// Whether we consumed a token introducing a node (`-`, `?`, `:`, an anchor, a tag or an
+    /// alias) since the last event.
+    node_pending: bool,
+    /// The warnings encountered so far.
+    warnings: Vec<Warning>,
+    /// The options of the parser.
+    options: ParserOptions,
+    /// The number of events emitted so far, comments excluded.
+    event_count: usize,
+    /// The number of documents started so far.
+    document_count: usize,
+    /// The number of aliases emitted so far.
+    alias_count: usize,
+    /// The number of nodes emitted so far.
+    node_count: usize,
+    /// The number of nodes emitted so far, once aliases are expanded.
+    expanded_node_count: usize,
+    /// The number of nodes anchored collections amount to once aliases are expanded, by anchor
+    /// ID.
+    anchor_sizes: HashMap<usize, usize>,
 }
 
 /// Trait to be implemented in order to use the low-level parsing API.
@@ -183,19 +367,19 @@ pub struct Parser<T> {
 /// # use saphyr_parser::{Event, EventReceiver, Parser};
 /// #
 /// /// Sink of events. Collects them into an array.
-/// struct EventSink {
-///     events: Vec<Event>,
+/// struct EventSink<'input> {
+///     events: Vec<Event<'input>>,
 /// }
 ///
 /// /// Implement `on_event`, pushing into `self.events`.
-/// impl EventReceiver for EventSink {
-///     fn on_event(&mut self, ev: Event) {
+/// impl<'input> EventReceiver<'input> for EventSink<'input> {
+///     fn on_event(&mut self, ev: Event<'input>) {
 ///         self.events.push(ev);
 ///     }
 /// }
 ///
 /// /// Load events from a yaml string.
-/// fn str_to_events(yaml: &str) -> Vec<Event> {
+/// fn str_to_events(yaml: &str) -> Vec<Event<'_>> {
 ///     let mut sink = EventSink { events: Vec::new() };
 ///     let mut parser = Parser::new_from_str(yaml);
 ///     // Load events using our sink as the receiver.
@@ -203,81 +387,176 @@ pub struct Parser<T> {
 ///     sink.events
 /// }
 /// ```
-pub trait EventReceiver {
+pub trait EventReceiver<'input> {
     /// Handler called for each YAML event that is emitted by the parser.
-    fn on_event(&mut self, ev: Event);
+    fn on_event(&mut self, ev: Event<'input>);
 }
 
 /// Trait to be implemented for using the low-level parsing API.
 ///
 /// Functionally similar to [`EventReceiver`], but receives a [`Marker`] as well as the event.
-pub trait MarkedEventReceiver {
+/// The [`Marker`] is the one [`Parser::next_event`] returns along with the event. Note that
+/// [`MarkedEventReceiver`]s implement [`SpannedEventReceiver`] automatically.
+pub trait MarkedEventReceiver<'input> {
     /// Handler called for each event that occurs.
-    fn on_event(&mut self, ev: Event, _mark: Marker);
+    fn on_event(&mut self, ev: Event<'input>, _mark: Marker);
 }
 
-impl<R: EventReceiver> MarkedEventReceiver for R {
-    fn on_event(&mut self, ev: Event, _mark: Marker) {
+impl<'input, R: EventReceiver<'input>> MarkedEventReceiver<'input> for R {
+    fn on_event(&mut self, ev: Event<'input>, _mark: Marker) {
         self.on_event(ev);
     }
 }
 
+/// Trait to be implemented for using the low-level parsing API.
+///
+/// Functionally similar to [`EventReceiver`], but receives the [`Span`] of the event in the
+/// source as well as the event.
+///
+/// # Spans
+/// The span of a scalar or an alias covers the whole node, including quotes and block scalar
+/// headers, but not its properties (anchor and tag). The span of a [`Event::SequenceStart`] or
+/// [`Event::MappingStart`] event covers the opening `[` or `{` of flow collections and is empty
+/// at the first key or entry of block collections. Conversely, the span of the matching end event
+/// covers the closing `]` or `}`, or is empty right after the last node of block collections. The
+/// extent of a collection thus spans from the start of its start event to the end of its end
+/// event.
+///
+/// Events which are not backed by any character of the input (empty scalars, implicit document
+/// starts and ends, ...) have an empty span.
+pub trait SpannedEventReceiver<'input> {
+    /// Handler called for each event that occurs.
+    fn on_event(&mut self, ev: Event<'input>, span: Span);
+
+    /// Handler called by [`Parser::load`] for each event, along with the marker
+    /// [`Parser::next_event`] returns for it.
+    #[doc(hidden)]
+    fn on_marked_event(&mut self, ev: Event<'input>, span: Span, _mark: Marker) {
+        self.on_event(ev, span);
+    }
+}
+
+impl<'input, R: MarkedEventReceiver<'input>> SpannedEventReceiver<'input> for R {
+    fn on_event(&mut self, ev: Event<'input>, span: Span) {
+        MarkedEventReceiver::on_event(self, ev, span.start);
+    }
+
+    fn on_marked_event(&mut self, ev: Event<'input>, _span: Span, mark: Marker) {
+        MarkedEventReceiver::on_event(self, ev, mark);
+    }
+}
+
 /// A convenience alias for a `Result` of a parser event.
-pub type ParseResult = Result<(Event, Marker), ScanError>;
+pub type ParseResult<'input> = Result<(Event<'input>, Marker), ScanError>;
+
+/// A `Result` of a parser event along with its span, as the state machine returns them.
+type SpannedResult<'input> = Result<(Event<'input>, Span), ScanError>;
+
+/// A parser event along with the marker [`Parser::next_event`] returns for it, and its span.
+type MarkedSpannedEvent<'input> = ((Event<'input>, Marker), Span);
 
-impl<'a> Parser<core::str::Chars<'a>> {
+impl<'input> Parser<'input, StrInput<'input>> {
     /// Create a new instance of a parser from a &str.
+    ///
+    /// Scalars which appear verbatim in `value` are borrowed from it in the emitted events.
     #[must_use]
-    pub fn new_from_str(value: &'a str) -> Self {
-        Parser::new(value.chars())
+    pub fn new_from_str(value: &'input str) -> Self {
+        Parser::new(StrInput::new(value))
     }
 }
 
-impl<T: Iterator<Item = char>> Parser<T> {
+impl<R: std::io::Read> Parser<'_, ReaderInput<R>> {
+    /// Create a new instance of a parser reading UTF-8 from the given reader.
+    ///
+    /// The input is read and decoded as parsing goes, so that the whole input need not be loaded
+    /// in memory. I/O errors and invalid UTF-8 are reported as [`ScanError`]s at the position
+    /// where they occur.
+    pub fn new_from_reader(reader: R) -> Self {
+        Parser::new(ReaderInput::new(reader))
+    }
+}
+
+impl<'input, T: Input<'input>> Parser<'input, T> {
     /// Create a new instance of a parser from the given input of characters.
-    pub fn new(src: T) -> Parser<T> {
+    pub fn new(src: T) -> Parser<'input, T> {
         Parser {
             scanner: Scanner::new(src),
             states: Vec::new(),
             state: State::StreamStart,
             token: None,
             current: None,
+            delayed: None,
 
             anchors: HashMap::new(),
             // valid anchor_id starts from 1
             anchor_id_count: 1,
+            anchor_names: Vec::new(),
             tags: HashMap::new(),
             stream_end_emitted: false,
-            keep_tags: false,
+            last_token_end: Marker::new(0, 1, 0),
+            last_event_end: Marker::new(0, 1, 0),
+            marker: None,
+            errors: Vec::new(),
+            recovered: VecDeque::new(),
+            open: Vec::new(),
+            node_pending: false,
+            warnings: Vec::new(),
+            options: ParserOptions::default(),
+            event_count: 0,
+            document_count: 0,
+            alias_count: 0,
+            node_count: 0,
+            expanded_node_count: 0,
+            anchor_sizes: HashMap::new(),
         }
     }
 
+    /// Set the options of the parser and of its scanner.
+    ///
+    /// This replaces any option set before, including with [`Self::keep_tags`].
+    #[must_use]
+    pub fn options(mut self, options: ParserOptions) -> Self {
+        self.scanner = self.scanner.options(options);
+        self.options = options;
+        self
+    }
+
     /// Whether to keep tags across multiple documents when parsing.
     ///
-    /// This behavior is non-standard as per the YAML specification but can be encountered in the
-    /// wild. This boolean allows enabling this non-standard extension. This would result in the
-    /// parser accepting input from [test
-    /// QLJ7](https://github.com/yaml/yaml-test-suite/blob/ccfa74e56afb53da960847ff6e6976c0a0825709/src/QLJ7.yaml)
-    /// of the yaml-test-suite:
+    /// See [`ParserOptions::keep_tags`].
+    #[deprecated(note = "use `Parser::options` with `ParserOptions::keep_tags` instead")]
+    #[must_use]
+    pub fn keep_tags(self, value: bool) -> Self {
+        let options = self.options.keep_tags(value);
+        self.options(options)
+    }
+
+    /// Return the name of the anchor with the given ID.
     ///
-    /// ```yaml
-    /// %TAG !prefix! tag:example.com,2011:
-    /// --- !prefix!A
-    /// a: b
-    /// --- !prefix!B
-    /// c: d
-    /// --- !prefix!C
-    /// e: f
-    /// ```
+    /// IDs are those found in [`Event::Alias`], [`Event::Scalar`], [`Event::SequenceStart`] and
+    /// [`Event::MappingStart`] events emitted by this parser. Returns `None` if no anchor with
+    /// that ID has been encountered yet (including for ID 0, which denotes the absence of an
+    /// anchor).
+    #[must_use]
+    pub fn anchor_name(&self, id: usize) -> Option<&str> {
+        self.anchor_names.get(id.checked_sub(1)?).map(AsRef::as_ref)
+    }
+
+    /// Return the errors encountered so far when recovering from errors.
     ///
-    /// With `keep_tags` set to `false`, the above YAML is rejected. As per the specification, tags
-    /// only apply to the document immediately following them. This would error on `!prefix!B`.
+    /// This is always empty unless [`ParserOptions::recover_from_errors`] is set.
+    #[must_use]
+    pub fn errors(&self) -> &[ScanError] {
+        &self.errors
+    }
+
+    /// Return the warnings encountered so far.
     ///
-    /// With `keep_tags` set to `true`, the above YAML is accepted by the parser.
+    /// Warnings report unusual input which is nonetheless parsed, such as unknown directives.
+    /// See [`WarningKind`].
     #[must_use]
-    pub fn keep_tags(mut self, value: bool) -> Self {
-        self.keep_tags = value;
-        self
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
     }
 
     /// Try to load the next event and return it, but do not consuming it from `self`.
@@ -287,8 +566,8 @@ impl<T: Iterator<Item = char>> Parser<T> {
     ///
     /// # Errors
     /// Returns `ScanError` when loading the next event fails.
-    pub fn peek(&mut self) -> Option<Result<&(Event, Marker), ScanError>> {
-        if let Some(ref x) = self.current {
+    pub fn peek(&mut self) -> Option<Result<&(Event<'input>, Marker), ScanError>> {
+        if let Some((ref x, _)) = self.current {
             Some(Ok(x))
         } else {
             if self.stream_end_emitted {
@@ -298,7 +577,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
                 Ok(token) => self.current = Some(token),
                 Err(e) => return Some(Err(e)),
             }
-            self.current.as_ref().map(Ok)
+            self.current.as_ref().map(|(x, _)| Ok(x))
         }
     }
 
@@ -306,12 +585,12 @@ impl<T: Iterator<Item = char>> Parser<T> {
     ///
     /// # Errors
     /// Returns `ScanError` when loading the next event fails.
-    pub fn next_event(&mut self) -> Option<ParseResult> {
+    pub fn next_event(&mut self) -> Option<ParseResult<'input>> {
         if self.stream_end_emitted {
             return None;
         }
 
-        let tok = self.next_event_impl();
+        let tok = self.next_event_impl().map(|(event, _)| event);
         if matches!(tok, Ok((Event::StreamEnd, _))) {
             self.stream_end_emitted = true;
         }
@@ -323,7 +602,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
     /// [`Self::next_event`] should conform to the expectations of an [`Iterator`] and return an
     /// option. This burdens the parser code. This function is used internally when an option is
     /// undesirable.
-    fn next_event_impl(&mut self) -> ParseResult {
+    fn next_event_impl(&mut self) -> Result<MarkedSpannedEvent<'input>, ScanError> {
         match self.current.take() {
             None => self.parse(),
             Some(v) => Ok(v),
@@ -331,11 +610,11 @@ impl<T: Iterator<Item = char>> Parser<T> {
     }
 
     /// Peek at the next token from the scanner.
-    fn peek_token(&mut self) -> Result<&Token, ScanError> {
+    fn peek_token(&mut self) -> Result<&Token<'input>, ScanError> {
         match self.token {
             None => {
-                self.token = Some(self.scan_next_token()?);
-                Ok(self.token.as_ref().unwrap())
+                let token = self.scan_next_token()?;
+                Ok(self.token.insert(token))
             }
             Some(ref tok) => Ok(tok),
         }
@@ -344,42 +623,361 @@ impl<T: Iterator<Item = char>> Parser<T> {
     /// Extract and return the next token from the scanner.
     ///
     /// This function does _not_ make use of `self.token`.
-    fn scan_next_token(&mut self) -> Result<Token, ScanError> {
+    fn scan_next_token(&mut self) -> Result<Token<'input>, ScanError> {
         let token = self.scanner.next();
+        self.warnings.extend(self.scanner.take_warnings());
         match token {
             None => match self.scanner.get_error() {
-                None => Err(ScanError::new_str(self.scanner.mark(), "unexpected eof")),
+                None => Err(ScanError::from_kind(
+                    self.scanner.mark(),
+                    ScanErrorKind::UnexpectedEof,
+                )),
                 Some(e) => Err(e),
             },
             Some(tok) => Ok(tok),
         }
     }
 
-    fn fetch_token(&mut self) -> Token {
-        self.token
-            .take()
-            .expect("fetch_token needs to be preceded by peek_token")
+    /// Take the token returned by the last call to [`Self::peek_token`].
+    ///
+    /// # Errors
+    /// Returns an [`ScanErrorKind::InconsistentState`] error if no token was peeked.
+    fn fetch_token(&mut self) -> Result<Token<'input>, ScanError> {
+        let token = self.token.take().ok_or_else(|| self.inconsistent_state())?;
+        self.consume(&token);
+        Ok(token)
     }
 
     /// Skip the next token from the scanner.
     fn skip(&mut self) {
-        self.token = None;
+        if let Some(token) = self.token.take() {
+            self.consume(&token);
+        }
     }
+
+    /// Record that the given token has been consumed.
+    fn consume(&mut self, Token(span, tok): &Token<'input>) {
+        if !span.is_empty() {
+            self.last_token_end = span.end;
+        }
+        if matches!(
+            tok,
+            TokenType::BlockEntry
+                | TokenType::Key
+                | TokenType::Value
+                | TokenType::Anchor(_)
+                | TokenType::Tag(..)
+                | TokenType::Alias(_)
+        ) {
+            self.node_pending = true;
+        }
+    }
+
+    /// Return an empty span right after the last token we consumed.
+    fn empty_span(&self) -> Span {
+        Span::empty(self.last_token_end)
+    }
+
     /// Pops the top-most state and make it the current state.
-    fn pop_state(&mut self) {
-        self.state = self.states.pop().unwrap();
+    fn pop_state(&mut self) -> Result<(), ScanError> {
+        self.state = self.states.pop().ok_or_else(|| self.inconsistent_state())?;
+        Ok(())
+    }
+
+    /// Return the error for a state of the parser that should be impossible.
+    fn inconsistent_state(&self) -> ScanError {
+        ScanError::from_kind(self.scanner.mark(), ScanErrorKind::InconsistentState)
     }
+
     /// Push a new state atop the state stack.
     fn push_state(&mut self, state: State) {
         self.states.push(state);
     }
 
-    fn parse(&mut self) -> ParseResult {
-        if self.state == State::End {
-            return Ok((Event::StreamEnd, self.scanner.mark()));
+    fn parse(&mut self) -> Result<MarkedSpannedEvent<'input>, ScanError> {
+        let (ev, span) = match self.delayed.take() {
+            Some(delayed) => delayed,
+            None => self.next_state_machine_event()?,
+        };
+        if let Some(comment) = self.scanner.pop_comment_before(span.start) {
+            self.delayed = Some((ev, span));
+            return Ok((
+                (Event::Comment(comment.text, comment.style), comment.span.start),
+                comment.span,
+            ));
+        }
+        Ok((ev, span))
+    }
+
+    /// Run the state machine for the next event, recovering from errors if requested.
+    fn next_state_machine_event(&mut self) -> Result<MarkedSpannedEvent<'input>, ScanError> {
+        loop {
+            if let Some((event, span)) = self.recovered.pop_front() {
+                return Ok(((event, span.start), span));
+            }
+            if self.state == State::End {
+                let mark = self.scanner.mark();
+                return Ok(((Event::StreamEnd, mark), Span::empty(mark)));
+            }
+            let result = self.state_machine();
+            let mark = self.marker.take();
+            match result {
+                Ok(event) => {
+                    self.check_limits(&event)?;
+                    self.track(&event.0, event.1);
+                    // Empty nodes that follow must not be placed before this event.
+                    if event.1.start.index() > self.last_token_end.index() {
+                        self.last_token_end = event.1.start;
+                    }
+                    if event.1.end.index() > self.last_event_end.index() {
+                        self.last_event_end = event.1.end;
+                    }
+                    self.node_pending = false;
+                    let mark = mark.unwrap_or_else(|| self.scanner.token_marker());
+                    return Ok(((event.0, mark), event.1));
+                }
+                Err(e) => {
+                    // Errors from the scanner already hold the flow collections it is in, which
+                    // may not have been parsed yet.
+                    let scanned_flow = e.context().iter().any(|(context, _)| {
+                        matches!(
+                            context,
+                            ErrorContext::FlowMapping | ErrorContext::FlowSequence
+                        )
+                    });
+                    let e = self
+                        .open
+                        .iter()
+                        .rev()
+                        .filter(|open| !(scanned_flow && open.is_flow()))
+                        .fold(e, |e, open| {
+                            e.with_context(open.error_context(), open.start)
+                        });
+                    // Exceeding a limit is not recoverable.
+                    if !self.options.recover_from_errors || e.kind().is_fatal() {
+                        return Err(e);
+                    }
+                    self.recover_from(e)?;
+                }
+            }
+        }
+    }
+
+    /// Check that emitting `event` does not exceed [`ParserOptions::limits`].
+    fn check_limits(&mut self, (event, span): &(Event<'input>, Span)) -> Result<(), ScanError> {
+        self.event_count += 1;
+        let exceeded = match event {
+            _ if self.event_count > self.options.limits.max_events => {
+                ScanErrorKind::EventLimitExceeded
+            }
+            Event::DocumentStart(..) => {
+                self.document_count += 1;
+                if self.document_count <= self.options.limits.max_documents {
+                    return Ok(());
+                }
+                ScanErrorKind::DocumentLimitExceeded
+            }
+            // The document the collection is in is open as well.
+            Event::SequenceStart(..) | Event::MappingStart(..)
+                if self.open.len() > self.options.limits.max_depth =>
+            {
+                ScanErrorKind::DepthLimitExceeded
+            }
+            &Event::Alias(id) => {
+                self.alias_count += 1;
+                let expanded = self
+                    .expanded_node_count
+                    .saturating_add(self.anchor_size(id));
+                let max_ratio = self
+                    .options
+                    .limits
+                    .max_expansion_ratio
+                    .saturating_mul(self.node_count + 1);
+                if self.alias_count > self.options.limits.max_aliases {
+                    ScanErrorKind::AliasLimitExceeded
+                } else if expanded > self.options.limits.max_expanded_nodes {
+                    ScanErrorKind::ExpandedNodeLimitExceeded
+                } else if expanded > max_ratio {
+                    ScanErrorKind::ExpansionRatioExceeded
+                } else {
+                    return Ok(());
+                }
+            }
+            _ => return Ok(()),
+        };
+        Err(ScanError::from_kind(span.start, exceeded))
+    }
+
+    /// Update [`Self::open`] with an event we are about to emit.
+    fn track(&mut self, event: &Event<'input>, span: Span) {
+        let (container, anchor) = match *event {
+            Event::DocumentStart(..) => (Container::Document, 0),
+            Event::SequenceStart(anchor, _, style) => (Container::Sequence(style), anchor),
+            Event::MappingStart(anchor, _, style) => (Container::Mapping(style), anchor),
+            Event::DocumentEnd(_) | Event::SequenceEnd | Event::MappingEnd => {
+                if let Some(done) = self.open.pop() {
+                    if done.anchor > 0 {
+                        self.anchor_sizes.insert(done.anchor, done.expanded);
+                    }
+                    self.add_to_parent(done.expanded);
+                }
+                return;
+            }
+            Event::Scalar(..) | Event::Invalid => {
+                self.count_node(1);
+                self.add_to_parent(1);
+                return;
+            }
+            Event::Alias(id) => {
+                let size = self.anchor_size(id);
+                self.count_node(size);
+                self.add_to_parent(size);
+                return;
+            }
+            _ => return,
+        };
+        if container != Container::Document {
+            self.count_node(1);
+        }
+        self.open.push(OpenContainer {
+            container,
+            start: span.start,
+            anchor,
+            nodes: 0,
+            expanded: 1,
+        });
+    }
+
+    /// Record a node of the input, which amounts to `expanded` nodes once aliases are expanded.
+    fn count_node(&mut self, expanded: usize) {
+        self.node_count += 1;
+        self.expanded_node_count = self.expanded_node_count.saturating_add(expanded);
+    }
+
+    /// Add a complete node, which amounts to `expanded` nodes once aliases are expanded, to the
+    /// innermost open container.
+    fn add_to_parent(&mut self, expanded: usize) {
+        if let Some(parent) = self.open.last_mut() {
+            parent.nodes += 1;
+            parent.expanded = parent.expanded.saturating_add(expanded);
+        }
+    }
+
+    /// Return the number of nodes the anchor with the given ID stands for once aliases are
+    /// expanded.
+    ///
+    /// Collections which are still open (i.e.: recursive aliases) count as a single node.
+    fn anchor_size(&self, id: usize) -> usize {
+        self.anchor_sizes.get(&id).copied().unwrap_or(1)
+    }
+
+    /// Queue an event generated when recovering from an error.
+    ///
+    /// # Errors
+    /// Returns an error if emitting the event exceeds [`ParserOptions::limits`].
+    fn push_recovered(&mut self, event: Event<'input>, span: Span) -> Result<(), ScanError> {
+        let event = (event, span);
+        self.check_limits(&event)?;
+        self.track(&event.0, span);
+        // Empty nodes that follow must not be placed before this event.
+        if span.start.index() > self.last_token_end.index() {
+            self.last_token_end = span.start;
+        }
+        self.recovered.push_back(event);
+        Ok(())
+    }
+
+    /// Record `error` and bring the parser back to a state from which it can resume parsing.
+    ///
+    /// Flow collections we are in are closed and the scanner skips input up to the next line.
+    /// Parsing then resumes in the innermost block collection. If the stream has ended, all
+    /// collections and the document are closed instead.
+    ///
+    /// # Errors
+    /// Returns an error if the events we generate exceed [`ParserOptions::limits`].
+    fn recover_from(&mut self, error: ScanError) -> Result<(), ScanError> {
+        // The events we generate are placed at the error, unless events emitted so far go past it.
+        let span = if error.marker().index() < self.last_event_end.index() {
+            Span::empty(self.last_event_end)
+        } else {
+            Span::empty(*error.marker())
+        };
+        let at_stream_end = match self.token {
+            Some(Token(_, TokenType::StreamEnd)) => true,
+            Some(_) => false,
+            None => self.scanner.stream_ended(),
+        };
+        let line = if self.scanner.get_error().is_some() {
+            self.scanner.mark().line()
+        } else {
+            error.marker().line()
+        };
+        self.errors.push(error);
+        if !at_stream_end {
+            // The offending token, if any, is dropped so that we make progress.
+            self.token = None;
+            self.scanner.resync(line);
+        }
+
+        if self.node_pending && !self.open.is_empty() {
+            self.push_recovered(Event::Invalid, span)?;
+        }
+        while let Some(&top) = self.open.last() {
+            if !at_stream_end && !top.is_flow() {
+                break;
+            }
+            if top.lacks_value() || (top.container == Container::Document && top.nodes == 0) {
+                self.push_recovered(Event::Invalid, span)?;
+            }
+            let end = match top.container {
+                Container::Document => Event::DocumentEnd(false),
+                Container::Sequence(_) => Event::SequenceEnd,
+                Container::Mapping(_) => Event::MappingEnd,
+            };
+            self.push_recovered(end, span)?;
+        }
+        if let Some(&top) = self.open.last() {
+            if top.lacks_value() {
+                self.push_recovered(Event::Invalid, span)?;
+            }
+        }
+
+        self.states.clear();
+        self.state = match self.open.split_last() {
+            _ if at_stream_end => State::End,
+            None => State::ImplicitDocumentStart,
+            Some((&top, parents)) => {
+                self.states
+                    .extend(parents.iter().map(|parent| parent.resume_state()));
+                match top.container {
+                    Container::Document if top.nodes == 0 => {
+                        self.states.push(State::DocumentEnd);
+                        State::DocumentContent
+                    }
+                    Container::Document => State::DocumentEnd,
+                    Container::Sequence(CollectionStyle::Indentless) => {
+                        State::IndentlessSequenceEntry
+                    }
+                    Container::Sequence(_) => State::BlockSequenceEntry,
+                    Container::Mapping(_) => State::BlockMappingKey,
+                }
+            }
+        };
+        self.node_pending = false;
+        Ok(())
+    }
+
+    /// Return the next event that is not a comment, sending comments to `recv`.
+    fn next_non_comment_event<R: SpannedEventReceiver<'input>>(
+        &mut self,
+        recv: &mut R,
+    ) -> Result<MarkedSpannedEvent<'input>, ScanError> {
+        loop {
+            match self.next_event_impl()? {
+                ((ev @ Event::Comment(..), mark), span) => recv.on_marked_event(ev, span, mark),
+                event => return Ok(event),
+            }
         }
-        let (ev, mark) = self.state_machine()?;
-        Ok((ev, mark))
     }
 
     /// Load the YAML from the stream in `self`, pushing events into `recv`.
@@ -390,40 +988,39 @@ impl<T: Iterator<Item = char>> Parser<T> {
     /// If `multi` is set to `true`, the parser will allow parsing of multiple YAML documents
     /// inside the stream.
     ///
-    /// Note that any [`EventReceiver`] is also a [`MarkedEventReceiver`], so implementing the
-    /// former is enough to call this function.
+    /// Note that any [`EventReceiver`] is also a [`MarkedEventReceiver`] and a
+    /// [`SpannedEventReceiver`], so implementing the former is enough to call this function.
     /// # Errors
     /// Returns `ScanError` when loading fails.
-    pub fn load<R: MarkedEventReceiver>(
+    pub fn load<R: SpannedEventReceiver<'input>>(
         &mut self,
         recv: &mut R,
         multi: bool,
     ) -> Result<(), ScanError> {
         if !self.scanner.stream_started() {
-            let (ev, mark) = self.next_event_impl()?;
+            let ((ev, mark), span) = self.next_non_comment_event(recv)?;
             if ev != Event::StreamStart {
-                return Err(ScanError::new_str(
+                return Err(ScanError::from_kind(
                     mark,
-                    "did not find expected <stream-start>",
+                    ScanErrorKind::ExpectedStreamStart,
                 ));
             }
-            recv.on_event(ev, mark);
+            recv.on_marked_event(ev, span, mark);
         }
 
         if self.scanner.stream_ended() {
             // XXX has parsed?
-            recv.on_event(Event::StreamEnd, self.scanner.mark());
+            let mark = self.scanner.mark();
+            recv.on_marked_event(Event::StreamEnd, Span::empty(mark), mark);
             return Ok(());
         }
         loop {
-            let (ev, mark) = self.next_event_impl()?;
+            let ((ev, mark), span) = self.next_non_comment_event(recv)?;
             if ev == Event::StreamEnd {
-                recv.on_event(ev, mark);
+                recv.on_marked_event(ev, span, mark);
                 return Ok(());
             }
-            // clear anchors before a new document
-            self.anchors.clear();
-            self.load_document(ev, mark, recv)?;
+            self.load_document(((ev, mark), span), recv)?;
             if !multi {
                 break;
             }
@@ -431,91 +1028,68 @@ impl<T: Iterator<Item = char>> Parser<T> {
         Ok(())
     }
 
-    fn load_document<R: MarkedEventReceiver>(
+    fn load_document<R: SpannedEventReceiver<'input>>(
         &mut self,
-        first_ev: Event,
-        mark: Marker,
+        ((first_ev, mark), span): MarkedSpannedEvent<'input>,
         recv: &mut R,
     ) -> Result<(), ScanError> {
-        if first_ev != Event::DocumentStart {
-            return Err(ScanError::new_str(
+        if !matches!(first_ev, Event::DocumentStart(..)) {
+            return Err(ScanError::from_kind(
                 mark,
-                "did not find expected <document-start>",
+                ScanErrorKind::ExpectedDocumentStart,
             ));
         }
-        recv.on_event(first_ev, mark);
+        recv.on_marked_event(first_ev, span, mark);
 
-        let (ev, mark) = self.next_event_impl()?;
-        self.load_node(ev, mark, recv)?;
+        let first_node = self.next_non_comment_event(recv)?;
+        self.load_node(first_node, recv)?;
 
         // DOCUMENT-END is expected.
-        let (ev, mark) = self.next_event_impl()?;
-        assert_eq!(ev, Event::DocumentEnd);
-        recv.on_event(ev, mark);
+        let ((ev, mark), span) = self.next_non_comment_event(recv)?;
+        if !matches!(ev, Event::DocumentEnd(_)) {
+            return Err(ScanError::from_kind(
+                mark,
+                ScanErrorKind::ExpectedDocumentEnd,
+            ));
+        }
+        recv.on_marked_event(ev, span, mark);
 
         Ok(())
     }
 
-    fn load_node<R: MarkedEventReceiver>(
+    /// Send the events of the node starting with `first` to `recv`, up to the end of the node.
+    ///
+    /// The events of the state machine are well-formed, so we only need to count how deep we are
+    /// in collections to find the end of the node. This is done iteratively, so that deeply nested
+    /// input does not overflow the stack.
+    fn load_node<R: SpannedEventReceiver<'input>>(
         &mut self,
-        first_ev: Event,
-        mark: Marker,
+        first: MarkedSpannedEvent<'input>,
         recv: &mut R,
     ) -> Result<(), ScanError> {
-        match first_ev {
-            Event::Alias(..) | Event::Scalar(..) => {
-                recv.on_event(first_ev, mark);
-                Ok(())
-            }
-            Event::SequenceStart(..) => {
-                recv.on_event(first_ev, mark);
-                self.load_sequence(recv)
-            }
-            Event::MappingStart(..) => {
-                recv.on_event(first_ev, mark);
-                self.load_mapping(recv)
+        let mut depth = 0usize;
+        let ((mut ev, mut mark), mut span) = first;
+        loop {
+            match ev {
+                Event::Alias(..) | Event::Scalar(..) | Event::Invalid => {}
+                Event::SequenceStart(..) | Event::MappingStart(..) => depth += 1,
+                Event::SequenceEnd | Event::MappingEnd if depth > 0 => depth -= 1,
+                _ => {
+                    return Err(ScanError::from_kind(
+                        mark,
+                        ScanErrorKind::InconsistentState,
+                    ))
+                }
             }
-            _ => {
-                println!("UNREACHABLE EVENT: {first_ev:?}");
-                unreachable!();
+            recv.on_marked_event(ev, span, mark);
+            if depth == 0 {
+                return Ok(());
             }
+            ((ev, mark), span) = self.next_non_comment_event(recv)?;
         }
     }
 
-    fn load_mapping<R: MarkedEventReceiver>(&mut self, recv: &mut R) -> Result<(), ScanError> {
-        let (mut key_ev, mut key_mark) = self.next_event_impl()?;
-        while key_ev != Event::MappingEnd {
-            // key
-            self.load_node(key_ev, key_mark, recv)?;
-
-            // value
-            let (ev, mark) = self.next_event_impl()?;
-            self.load_node(ev, mark, recv)?;
-
-            // next event
-            let (ev, mark) = self.next_event_impl()?;
-            key_ev = ev;
-            key_mark = mark;
-        }
-        recv.on_event(key_ev, key_mark);
-        Ok(())
-    }
-
-    fn load_sequence<R: MarkedEventReceiver>(&mut self, recv: &mut R) -> Result<(), ScanError> {
-        let (mut ev, mut mark) = self.next_event_impl()?;
-        while ev != Event::SequenceEnd {
-            self.load_node(ev, mark, recv)?;
-
-            // next event
-            let (next_ev, next_mark) = self.next_event_impl()?;
-            ev = next_ev;
-            mark = next_mark;
-        }
-        recv.on_event(ev, mark);
-        Ok(())
-    }
-
-    fn state_machine(&mut self) -> ParseResult {
+    fn state_machine(&mut self) -> SpannedResult<'input> {
         // let next_tok = self.peek_token().cloned()?;
         // println!("cur_state {:?}, next tok: {:?}", self.state, next_tok);
         debug_print!("\n\x1B[;33mParser state: {:?} \x1B[;0m", self.state);
@@ -553,49 +1127,54 @@ impl<T: Iterator<Item = char>> Parser<T> {
             State::FlowMappingEmptyValue => self.flow_mapping_value(true),
 
             /* impossible */
-            State::End => unreachable!(),
+            State::End => Err(ScanError::from_kind(
+                self.scanner.mark(),
+                ScanErrorKind::InconsistentState,
+            )),
         }
     }
 
-    fn stream_start(&mut self) -> ParseResult {
+    fn stream_start(&mut self) -> SpannedResult<'input> {
         match *self.peek_token()? {
-            Token(mark, TokenType::StreamStart(_)) => {
+            Token(span, TokenType::StreamStart(_)) => {
                 self.state = State::ImplicitDocumentStart;
                 self.skip();
-                Ok((Event::StreamStart, mark))
+                Ok((Event::StreamStart, span))
             }
-            Token(mark, _) => Err(ScanError::new_str(
-                mark,
-                "did not find expected <stream-start>",
+            ref token => Err(unexpected_token(
+                token,
+                ScanErrorKind::ExpectedStreamStart,
+                &[TokenKind::StreamStart],
             )),
         }
     }
 
-    fn document_start(&mut self, implicit: bool) -> ParseResult {
+    fn document_start(&mut self, implicit: bool) -> SpannedResult<'input> {
         while let TokenType::DocumentEnd = self.peek_token()?.1 {
             self.skip();
         }
 
         match *self.peek_token()? {
-            Token(mark, TokenType::StreamEnd) => {
+            Token(span, TokenType::StreamEnd) => {
                 self.state = State::End;
                 self.skip();
-                Ok((Event::StreamEnd, mark))
+                Ok((Event::StreamEnd, span))
             }
             Token(
                 _,
                 TokenType::VersionDirective(..)
                 | TokenType::TagDirective(..)
+                | TokenType::ReservedDirective(_)
                 | TokenType::DocumentStart,
             ) => {
                 // explicit document
                 self.explicit_document_start()
             }
-            Token(mark, _) if implicit => {
-                self.parser_process_directives()?;
+            Token(span, _) if implicit => {
+                let event = self.parser_process_directives(false)?;
                 self.push_state(State::DocumentEnd);
                 self.state = State::BlockNode;
-                Ok((Event::DocumentStart, mark))
+                Ok((event, Span::empty(span.start)))
             }
             _ => {
                 // explicit document
@@ -604,204 +1183,317 @@ impl<T: Iterator<Item = char>> Parser<T> {
         }
     }
 
-    fn parser_process_directives(&mut self) -> Result<(), ScanError> {
-        let mut version_directive_received = false;
+    /// Process the directives at the start of a document.
+    ///
+    /// Returns the [`Event::DocumentStart`] event holding them. Tag directives are also added to
+    /// [`Self::tags`].
+    fn parser_process_directives(&mut self, explicit: bool) -> Result<Event<'input>, ScanError> {
+        // Anchors are local to their document.
+        self.anchors.clear();
+        let mut version = None;
+        let mut tags: Vec<(String, String)> = Vec::new();
         loop {
-            let mut tags = HashMap::new();
             match self.peek_token()? {
-                Token(mark, TokenType::VersionDirective(_, _)) => {
-                    // XXX parsing with warning according to spec
-                    //if major != 1 || minor > 2 {
-                    //    return Err(ScanError::new_str(tok.0,
-                    //        "found incompatible YAML document"));
-                    //}
-                    if version_directive_received {
-                        return Err(ScanError::new_str(*mark, "duplicate version directive"));
+                &Token(span, TokenType::VersionDirective(major, minor)) => {
+                    if version.is_some() {
+                        return Err(ScanError::from_kind(
+                            span.start,
+                            ScanErrorKind::DuplicateVersionDirective,
+                        ));
                     }
-                    version_directive_received = true;
+                    if major == 1 && minor > 2 {
+                        self.warnings.push(Warning::new(
+                            span.start,
+                            WarningKind::HigherMinorVersion(major, minor),
+                        ));
+                    }
+                    version = Some((major, minor));
+                }
+                Token(span, TokenType::ReservedDirective(name)) => {
+                    let warning =
+                        Warning::new(span.start, WarningKind::UnknownDirective(name.clone()));
+                    self.warnings.push(warning);
                 }
-                Token(mark, TokenType::TagDirective(handle, prefix)) => {
-                    if tags.contains_key(handle) {
-                        return Err(ScanError::new_str(*mark, "the TAG directive must only be given at most once per handle in the same document"));
+                Token(span, TokenType::TagDirective(handle, prefix)) => {
+                    if tags.iter().any(|(h, _)| h == handle) {
+                        return Err(ScanError::from_kind(
+                            span.start,
+                            ScanErrorKind::DuplicateTagDirective(handle.clone()),
+                        ));
                     }
-                    tags.insert(handle.to_string(), prefix.to_string());
+                    tags.push((handle.clone(), prefix.clone()));
                 }
                 _ => break,
             }
-            self.tags = tags;
             self.skip();
         }
-        Ok(())
+        let mut inherited_tags: Vec<_> = self
+            .tags
+            .iter()
+            .filter(|(handle, _)| !tags.iter().any(|(h, _)| h == *handle))
+            .map(|(handle, prefix)| (handle.clone(), prefix.clone()))
+            .collect();
+        inherited_tags.sort_unstable();
+        self.tags.extend(tags.iter().cloned());
+        let yaml_version = version.map_or(self.options.yaml_version, |(major, minor)| {
+            YamlVersion::from_directive(major, minor)
+        });
+        Ok(Event::DocumentStart(
+            explicit,
+            version,
+            tags,
+            inherited_tags,
+            yaml_version,
+        ))
     }
 
-    fn explicit_document_start(&mut self) -> ParseResult {
-        self.parser_process_directives()?;
+    fn explicit_document_start(&mut self) -> SpannedResult<'input> {
+        let event = self.parser_process_directives(true)?;
         match *self.peek_token()? {
-            Token(mark, TokenType::DocumentStart) => {
+            Token(span, TokenType::DocumentStart) => {
                 self.push_state(State::DocumentEnd);
                 self.state = State::DocumentContent;
                 self.skip();
-                Ok((Event::DocumentStart, mark))
+                Ok((event, span))
             }
-            Token(mark, _) => Err(ScanError::new_str(
-                mark,
-                "did not find expected <document start>",
+            ref token => Err(unexpected_token(
+                token,
+                ScanErrorKind::ExpectedDocumentStart,
+                &[
+                    TokenKind::VersionDirective,
+                    TokenKind::TagDirective,
+                    TokenKind::ReservedDirective,
+                    TokenKind::DocumentStart,
+                ],
             )),
         }
     }
 
-    fn document_content(&mut self) -> ParseResult {
+    fn document_content(&mut self) -> SpannedResult<'input> {
         match *self.peek_token()? {
             Token(
-                mark,
+                _,
                 TokenType::VersionDirective(..)
                 | TokenType::TagDirective(..)
+                | TokenType::ReservedDirective(_)
                 | TokenType::DocumentStart
                 | TokenType::DocumentEnd
                 | TokenType::StreamEnd,
             ) => {
-                self.pop_state();
+                self.pop_state()?;
                 // empty scalar
-                Ok((Event::empty_scalar(), mark))
+                Ok((Event::empty_scalar(), self.empty_span()))
             }
             _ => self.parse_node(true, false),
         }
     }
 
-    fn document_end(&mut self) -> ParseResult {
+    fn document_end(&mut self) -> SpannedResult<'input> {
         let mut explicit_end = false;
-        let marker: Marker = match *self.peek_token()? {
-            Token(mark, TokenType::DocumentEnd) => {
+        let span: Span = match *self.peek_token()? {
+            Token(span, TokenType::DocumentEnd) => {
                 explicit_end = true;
+                // The end is reported at the `...`, rather than at the token that follows.
+                self.marker = Some(span.start);
                 self.skip();
-                mark
+                span
             }
-            Token(mark, _) => mark,
+            _ => self.empty_span(),
         };
 
-        if !self.keep_tags {
+        if !self.options.keep_tags {
             self.tags.clear();
         }
         if explicit_end {
             self.state = State::ImplicitDocumentStart;
         } else {
-            if let Token(mark, TokenType::VersionDirective(..) | TokenType::TagDirective(..)) =
-                *self.peek_token()?
+            if let token @ Token(
+                _,
+                TokenType::VersionDirective(..)
+                | TokenType::TagDirective(..)
+                | TokenType::ReservedDirective(_),
+            ) = self.peek_token()?
             {
-                return Err(ScanError::new_str(
-                    mark,
-                    "missing explicit document end marker before directive",
+                return Err(unexpected_token(
+                    token,
+                    ScanErrorKind::MissingDocumentEnd,
+                    &[TokenKind::DocumentEnd],
                 ));
             }
             self.state = State::DocumentStart;
         }
 
-        Ok((Event::DocumentEnd, marker))
+        Ok((Event::DocumentEnd(explicit_end), span))
     }
 
-    fn register_anchor(&mut self, name: String, _: &Marker) -> usize {
-        // anchors can be overridden/reused
-        // if self.anchors.contains_key(name) {
-        //     return Err(ScanError::new_str(*mark,
-        //         "while parsing anchor, found duplicated anchor"));
-        // }
+    /// Allocate an ID for the anchor `name`, defined at `mark`.
+    ///
+    /// Aliases can only refer to the anchor once [`Self::parse_node`] has emitted the start of its
+    /// node, so that they never refer to a node that could not be parsed.
+    fn register_anchor(
+        &mut self,
+        name: Cow<'input, str>,
+        mark: &Marker,
+    ) -> Result<usize, ScanError> {
+        if self.anchor_names.len() >= self.options.limits.max_anchors {
+            return Err(ScanError::from_kind(
+                *mark,
+                ScanErrorKind::AnchorLimitExceeded,
+            ));
+        }
+        // Anchors can be redefined, aliases then refer to the latest definition.
+        if self.anchors.contains_key(&name) {
+            self.warnings.push(Warning::new(
+                *mark,
+                WarningKind::RedefinedAnchor(name.to_string()),
+            ));
+        }
         let new_id = self.anchor_id_count;
         self.anchor_id_count += 1;
-        self.anchors.insert(name, new_id);
-        new_id
+        self.anchor_names.push(name);
+        Ok(new_id)
     }
 
-    fn parse_node(&mut self, block: bool, indentless_sequence: bool) -> ParseResult {
-        let mut anchor_id = 0;
+    fn parse_node(&mut self, block: bool, indentless_sequence: bool) -> SpannedResult<'input> {
+        let mut anchor = None;
         let mut tag = None;
         match *self.peek_token()? {
             Token(_, TokenType::Alias(_)) => {
-                self.pop_state();
-                if let Token(mark, TokenType::Alias(name)) = self.fetch_token() {
-                    match self.anchors.get(&name) {
-                        None => {
-                            return Err(ScanError::new_str(
-                                mark,
-                                "while parsing node, found unknown anchor",
-                            ))
-                        }
-                        Some(id) => return Ok((Event::Alias(*id), mark)),
-                    }
-                }
-                unreachable!()
+                self.pop_state()?;
+                return match self.fetch_token()? {
+                    Token(span, TokenType::Alias(name)) => match self.anchors.get(&name) {
+                        None => Err(ScanError::from_kind(
+                            span.start,
+                            ScanErrorKind::UnknownAnchor(name.into_owned()),
+                        )),
+                        Some(id) => Ok((Event::Alias(*id), span)),
+                    },
+                    _ => Err(self.inconsistent_state()),
+                };
             }
             Token(_, TokenType::Anchor(_)) => {
-                if let Token(mark, TokenType::Anchor(name)) = self.fetch_token() {
-                    anchor_id = self.register_anchor(name, &mark);
-                    if let TokenType::Tag(..) = self.peek_token()?.1 {
-                        if let TokenType::Tag(handle, suffix) = self.fetch_token().1 {
-                            tag = Some(self.resolve_tag(mark, &handle, suffix)?);
-                        } else {
-                            unreachable!()
-                        }
-                    }
-                } else {
-                    unreachable!()
+                let Token(span, TokenType::Anchor(name)) = self.fetch_token()? else {
+                    return Err(self.inconsistent_state());
+                };
+                anchor = Some((self.register_anchor(name.clone(), &span.start)?, name));
+                if let TokenType::Tag(..) = self.peek_token()?.1 {
+                    let Token(_, TokenType::Tag(handle, suffix)) = self.fetch_token()? else {
+                        return Err(self.inconsistent_state());
+                    };
+                    tag = Some(self.resolve_tag(span.start, &handle, suffix)?);
                 }
             }
-            Token(mark, TokenType::Tag(..)) => {
-                if let TokenType::Tag(handle, suffix) = self.fetch_token().1 {
-                    tag = Some(self.resolve_tag(mark, &handle, suffix)?);
-                    if let TokenType::Anchor(_) = &self.peek_token()?.1 {
-                        if let Token(mark, TokenType::Anchor(name)) = self.fetch_token() {
-                            anchor_id = self.register_anchor(name, &mark);
-                        } else {
-                            unreachable!()
-                        }
-                    }
-                } else {
-                    unreachable!()
+            Token(_, TokenType::Tag(..)) => {
+                let Token(span, TokenType::Tag(handle, suffix)) = self.fetch_token()? else {
+                    return Err(self.inconsistent_state());
+                };
+                tag = Some(self.resolve_tag(span.start, &handle, suffix)?);
+                if let TokenType::Anchor(_) = &self.peek_token()?.1 {
+                    let Token(span, TokenType::Anchor(name)) = self.fetch_token()? else {
+                        return Err(self.inconsistent_state());
+                    };
+                    anchor = Some((self.register_anchor(name.clone(), &span.start)?, name));
                 }
             }
             _ => {}
         }
+        let anchor_id = anchor.as_ref().map_or(0, |(id, _)| *id);
+        let event = self.parse_node_content(block, indentless_sequence, anchor_id, tag);
+        if let (Ok(_), Some((id, name))) = (&event, anchor) {
+            self.anchors.insert(name, id);
+        }
+        event
+    }
+
+    /// Parse the content of a node, after its properties.
+    fn parse_node_content(
+        &mut self,
+        block: bool,
+        indentless_sequence: bool,
+        anchor_id: usize,
+        tag: Option<Tag>,
+    ) -> SpannedResult<'input> {
         match *self.peek_token()? {
-            Token(mark, TokenType::BlockEntry) if indentless_sequence => {
+            Token(span, TokenType::BlockEntry) if indentless_sequence => {
                 self.state = State::IndentlessSequenceEntry;
-                Ok((Event::SequenceStart(anchor_id, tag), mark))
+                Ok((
+                    Event::SequenceStart(anchor_id, tag, CollectionStyle::Indentless),
+                    Span::empty(span.start),
+                ))
             }
             Token(_, TokenType::Scalar(..)) => {
-                self.pop_state();
-                if let Token(mark, TokenType::Scalar(style, v)) = self.fetch_token() {
-                    Ok((Event::Scalar(v, style, anchor_id, tag), mark))
-                } else {
-                    unreachable!()
+                self.pop_state()?;
+                match self.fetch_token()? {
+                    Token(span, TokenType::Scalar(style, v)) => {
+                        Ok((Event::Scalar(v, style, anchor_id, tag), span))
+                    }
+                    _ => Err(self.inconsistent_state()),
                 }
             }
-            Token(mark, TokenType::FlowSequenceStart) => {
+            Token(span, TokenType::FlowSequenceStart) => {
                 self.state = State::FlowSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor_id, tag), mark))
+                Ok((
+                    Event::SequenceStart(anchor_id, tag, CollectionStyle::Flow),
+                    span,
+                ))
             }
-            Token(mark, TokenType::FlowMappingStart) => {
+            Token(span, TokenType::FlowMappingStart) => {
                 self.state = State::FlowMappingFirstKey;
-                Ok((Event::MappingStart(anchor_id, tag), mark))
+                // The scanner emits an empty `FlowMappingStart` for implicit mappings (`[a: b]`).
+                let style = if span.is_empty() {
+                    CollectionStyle::FlowPair
+                } else {
+                    CollectionStyle::Flow
+                };
+                Ok((Event::MappingStart(anchor_id, tag, style), span))
             }
-            Token(mark, TokenType::BlockSequenceStart) if block => {
+            Token(span, TokenType::BlockSequenceStart) if block => {
                 self.state = State::BlockSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor_id, tag), mark))
+                Ok((
+                    Event::SequenceStart(anchor_id, tag, CollectionStyle::Block),
+                    span,
+                ))
             }
-            Token(mark, TokenType::BlockMappingStart) if block => {
+            Token(span, TokenType::BlockMappingStart) if block => {
                 self.state = State::BlockMappingFirstKey;
-                Ok((Event::MappingStart(anchor_id, tag), mark))
+                Ok((
+                    Event::MappingStart(anchor_id, tag, CollectionStyle::Block),
+                    span,
+                ))
             }
             // ex 7.2, an empty scalar can follow a secondary tag
-            Token(mark, _) if tag.is_some() || anchor_id > 0 => {
-                self.pop_state();
-                Ok((Event::empty_scalar_with_anchor(anchor_id, tag), mark))
+            Token(_, _) if tag.is_some() || anchor_id > 0 => {
+                self.pop_state()?;
+                Ok((
+                    Event::empty_scalar_with_anchor(anchor_id, tag),
+                    self.empty_span(),
+                ))
+            }
+            ref token => {
+                let mut expected = vec![
+                    TokenKind::Alias,
+                    TokenKind::Anchor,
+                    TokenKind::Tag,
+                    TokenKind::Scalar,
+                    TokenKind::FlowSequenceStart,
+                    TokenKind::FlowMappingStart,
+                ];
+                if block {
+                    expected.extend([TokenKind::BlockSequenceStart, TokenKind::BlockMappingStart]);
+                }
+                if indentless_sequence {
+                    expected.push(TokenKind::BlockEntry);
+                }
+                Err(unexpected_token(
+                    token,
+                    ScanErrorKind::ExpectedNodeContent,
+                    &expected,
+                ))
             }
-            Token(mark, _) => Err(ScanError::new_str(
-                mark,
-                "while parsing a node, did not find expected node content",
-            )),
         }
     }
 
-    fn block_mapping_key(&mut self, first: bool) -> ParseResult {
+    fn block_mapping_key(&mut self, first: bool) -> SpannedResult<'input> {
         // skip BlockMappingStart
         if first {
             let _ = self.peek_token()?;
@@ -811,139 +1503,146 @@ impl<T: Iterator<Item = char>> Parser<T> {
         match *self.peek_token()? {
             Token(_, TokenType::Key) => {
                 self.skip();
-                if let Token(mark, TokenType::Key | TokenType::Value | TokenType::BlockEnd) =
+                if let Token(_, TokenType::Key | TokenType::Value | TokenType::BlockEnd) =
                     *self.peek_token()?
                 {
                     self.state = State::BlockMappingValue;
                     // empty scalar
-                    Ok((Event::empty_scalar(), mark))
+                    Ok((Event::empty_scalar(), self.empty_span()))
                 } else {
                     self.push_state(State::BlockMappingValue);
                     self.parse_node(true, true)
                 }
             }
             // XXX(chenyh): libyaml failed to parse spec 1.2, ex8.18
-            Token(mark, TokenType::Value) => {
+            Token(_, TokenType::Value) => {
                 self.state = State::BlockMappingValue;
-                Ok((Event::empty_scalar(), mark))
+                Ok((Event::empty_scalar(), self.empty_span()))
             }
-            Token(mark, TokenType::BlockEnd) => {
-                self.pop_state();
+            Token(_, TokenType::BlockEnd) => {
+                self.pop_state()?;
                 self.skip();
-                Ok((Event::MappingEnd, mark))
+                Ok((Event::MappingEnd, self.empty_span()))
             }
-            Token(mark, _) => Err(ScanError::new_str(
-                mark,
-                "while parsing a block mapping, did not find expected key",
+            ref token => Err(unexpected_token(
+                token,
+                ScanErrorKind::ExpectedBlockMappingKey,
+                &[TokenKind::Key, TokenKind::Value, TokenKind::BlockEnd],
             )),
         }
     }
 
-    fn block_mapping_value(&mut self) -> ParseResult {
-        match *self.peek_token()? {
-            Token(_, TokenType::Value) => {
-                self.skip();
-                if let Token(mark, TokenType::Key | TokenType::Value | TokenType::BlockEnd) =
-                    *self.peek_token()?
-                {
-                    self.state = State::BlockMappingKey;
-                    // empty scalar
-                    Ok((Event::empty_scalar(), mark))
-                } else {
-                    self.push_state(State::BlockMappingKey);
-                    self.parse_node(true, true)
-                }
-            }
-            Token(mark, _) => {
+    fn block_mapping_value(&mut self) -> SpannedResult<'input> {
+        if let Token(_, TokenType::Value) = *self.peek_token()? {
+            self.skip();
+            if let Token(_, TokenType::Key | TokenType::Value | TokenType::BlockEnd) =
+                *self.peek_token()?
+            {
                 self.state = State::BlockMappingKey;
                 // empty scalar
-                Ok((Event::empty_scalar(), mark))
+                Ok((Event::empty_scalar(), self.empty_span()))
+            } else {
+                self.push_state(State::BlockMappingKey);
+                self.parse_node(true, true)
             }
+        } else {
+            self.state = State::BlockMappingKey;
+            // empty scalar
+            Ok((Event::empty_scalar(), self.empty_span()))
         }
     }
 
-    fn flow_mapping_key(&mut self, first: bool) -> ParseResult {
+    fn flow_mapping_key(&mut self, first: bool) -> SpannedResult<'input> {
         if first {
             let _ = self.peek_token()?;
             self.skip();
         }
-        let marker: Marker = {
-            match *self.peek_token()? {
-                Token(mark, TokenType::FlowMappingEnd) => mark,
-                Token(mark, _) => {
-                    if !first {
-                        match *self.peek_token()? {
-                            Token(_, TokenType::FlowEntry) => self.skip(),
-                            Token(mark, _) => return Err(ScanError::new_str(
-                                mark,
-                                "while parsing a flow mapping, did not find expected ',' or '}'",
-                            )),
-                        }
+        let span: Span = if let Token(span, TokenType::FlowMappingEnd) = *self.peek_token()? {
+            span
+        } else {
+            let mut entry_mark = None;
+            if !first {
+                match *self.peek_token()? {
+                    Token(_, TokenType::FlowEntry) => {
+                        entry_mark = Some(self.scanner.token_marker());
+                        self.skip();
                     }
-
-                    match *self.peek_token()? {
-                        Token(_, TokenType::Key) => {
-                            self.skip();
-                            if let Token(
-                                mark,
-                                TokenType::Value | TokenType::FlowEntry | TokenType::FlowMappingEnd,
-                            ) = *self.peek_token()?
-                            {
-                                self.state = State::FlowMappingValue;
-                                return Ok((Event::empty_scalar(), mark));
-                            }
-                            self.push_state(State::FlowMappingValue);
-                            return self.parse_node(false, false);
-                        }
-                        Token(marker, TokenType::Value) => {
-                            self.state = State::FlowMappingValue;
-                            return Ok((Event::empty_scalar(), marker));
-                        }
-                        Token(_, TokenType::FlowMappingEnd) => (),
-                        _ => {
-                            self.push_state(State::FlowMappingEmptyValue);
-                            return self.parse_node(false, false);
-                        }
+                    ref token => {
+                        return Err(unexpected_token(
+                            token,
+                            ScanErrorKind::ExpectedFlowMappingSeparator,
+                            &[TokenKind::FlowEntry, TokenKind::FlowMappingEnd],
+                        ))
                     }
+                }
+            }
 
-                    mark
+            match *self.peek_token()? {
+                Token(_, TokenType::Key) => {
+                    self.skip();
+                    if let Token(
+                        _,
+                        TokenType::Value | TokenType::FlowEntry | TokenType::FlowMappingEnd,
+                    ) = *self.peek_token()?
+                    {
+                        self.state = State::FlowMappingValue;
+                        return Ok((Event::empty_scalar(), self.empty_span()));
+                    }
+                    self.push_state(State::FlowMappingValue);
+                    return self.parse_node(false, false);
+                }
+                Token(_, TokenType::Value) => {
+                    self.state = State::FlowMappingValue;
+                    return Ok((Event::empty_scalar(), self.empty_span()));
+                }
+                Token(span, TokenType::FlowMappingEnd) => {
+                    // The end of the mapping is reported at its trailing `,`.
+                    self.marker = entry_mark;
+                    span
+                }
+                _ => {
+                    self.push_state(State::FlowMappingEmptyValue);
+                    return self.parse_node(false, false);
                 }
             }
         };
 
-        self.pop_state();
+        // Implicit flow mappings (`[a: b]`) end right after their last token.
+        let span = if span.is_empty() {
+            self.empty_span()
+        } else {
+            span
+        };
+        self.pop_state()?;
         self.skip();
-        Ok((Event::MappingEnd, marker))
+        Ok((Event::MappingEnd, span))
     }
 
-    fn flow_mapping_value(&mut self, empty: bool) -> ParseResult {
-        let mark: Marker = {
-            if empty {
-                let Token(mark, _) = *self.peek_token()?;
-                self.state = State::FlowMappingKey;
-                return Ok((Event::empty_scalar(), mark));
-            }
-            match *self.peek_token()? {
-                Token(marker, TokenType::Value) => {
-                    self.skip();
-                    match self.peek_token()?.1 {
-                        TokenType::FlowEntry | TokenType::FlowMappingEnd => {}
-                        _ => {
-                            self.push_state(State::FlowMappingKey);
-                            return self.parse_node(false, false);
-                        }
-                    }
-                    marker
+    fn flow_mapping_value(&mut self, empty: bool) -> SpannedResult<'input> {
+        if empty {
+            // The empty value is reported at the token that follows.
+            self.peek_token()?;
+            self.state = State::FlowMappingKey;
+            return Ok((Event::empty_scalar(), self.empty_span()));
+        }
+        if let Token(_, TokenType::Value) = *self.peek_token()? {
+            let value_mark = self.scanner.token_marker();
+            self.skip();
+            match self.peek_token()?.1 {
+                // The empty value is reported at its `:`.
+                TokenType::FlowEntry | TokenType::FlowMappingEnd => self.marker = Some(value_mark),
+                _ => {
+                    self.push_state(State::FlowMappingKey);
+                    return self.parse_node(false, false);
                 }
-                Token(marker, _) => marker,
             }
-        };
+        }
 
         self.state = State::FlowMappingKey;
-        Ok((Event::empty_scalar(), mark))
+        Ok((Event::empty_scalar(), self.empty_span()))
     }
 
-    fn flow_sequence_entry(&mut self, first: bool) -> ParseResult {
+    fn flow_sequence_entry(&mut self, first: bool) -> SpannedResult<'input> {
         // skip FlowMappingStart
         if first {
             let _ = self.peek_token()?;
@@ -951,32 +1650,36 @@ impl<T: Iterator<Item = char>> Parser<T> {
             self.skip();
         }
         match *self.peek_token()? {
-            Token(mark, TokenType::FlowSequenceEnd) => {
-                self.pop_state();
+            Token(span, TokenType::FlowSequenceEnd) => {
+                self.pop_state()?;
                 self.skip();
-                return Ok((Event::SequenceEnd, mark));
+                return Ok((Event::SequenceEnd, span));
             }
             Token(_, TokenType::FlowEntry) if !first => {
                 self.skip();
             }
-            Token(mark, _) if !first => {
-                return Err(ScanError::new_str(
-                    mark,
-                    "while parsing a flow sequence, expected ',' or ']'",
+            ref token if !first => {
+                return Err(unexpected_token(
+                    token,
+                    ScanErrorKind::ExpectedFlowSequenceSeparator,
+                    &[TokenKind::FlowEntry, TokenKind::FlowSequenceEnd],
                 ));
             }
             _ => { /* next */ }
         }
         match *self.peek_token()? {
-            Token(mark, TokenType::FlowSequenceEnd) => {
-                self.pop_state();
+            Token(span, TokenType::FlowSequenceEnd) => {
+                self.pop_state()?;
                 self.skip();
-                Ok((Event::SequenceEnd, mark))
+                Ok((Event::SequenceEnd, span))
             }
-            Token(mark, TokenType::Key) => {
+            Token(span, TokenType::Key) => {
                 self.state = State::FlowSequenceEntryMappingKey;
                 self.skip();
-                Ok((Event::MappingStart(0, None), mark))
+                Ok((
+                    Event::MappingStart(0, None, CollectionStyle::FlowPair),
+                    span,
+                ))
             }
             _ => {
                 self.push_state(State::FlowSequenceEntry);
@@ -985,29 +1688,26 @@ impl<T: Iterator<Item = char>> Parser<T> {
         }
     }
 
-    fn indentless_sequence_entry(&mut self) -> ParseResult {
-        match *self.peek_token()? {
-            Token(_, TokenType::BlockEntry) => (),
-            Token(mark, _) => {
-                self.pop_state();
-                return Ok((Event::SequenceEnd, mark));
-            }
+    fn indentless_sequence_entry(&mut self) -> SpannedResult<'input> {
+        if !matches!(self.peek_token()?.1, TokenType::BlockEntry) {
+            self.pop_state()?;
+            return Ok((Event::SequenceEnd, self.empty_span()));
         }
         self.skip();
         if let Token(
-            mark,
+            _,
             TokenType::BlockEntry | TokenType::Key | TokenType::Value | TokenType::BlockEnd,
         ) = *self.peek_token()?
         {
             self.state = State::IndentlessSequenceEntry;
-            Ok((Event::empty_scalar(), mark))
+            Ok((Event::empty_scalar(), self.empty_span()))
         } else {
             self.push_state(State::IndentlessSequenceEntry);
             self.parse_node(true, false)
         }
     }
 
-    fn block_sequence_entry(&mut self, first: bool) -> ParseResult {
+    fn block_sequence_entry(&mut self, first: bool) -> SpannedResult<'input> {
         // BLOCK-SEQUENCE-START
         if first {
             let _ = self.peek_token()?;
@@ -1015,69 +1715,68 @@ impl<T: Iterator<Item = char>> Parser<T> {
             self.skip();
         }
         match *self.peek_token()? {
-            Token(mark, TokenType::BlockEnd) => {
-                self.pop_state();
+            Token(_, TokenType::BlockEnd) => {
+                self.pop_state()?;
                 self.skip();
-                Ok((Event::SequenceEnd, mark))
+                Ok((Event::SequenceEnd, self.empty_span()))
             }
             Token(_, TokenType::BlockEntry) => {
                 self.skip();
-                if let Token(mark, TokenType::BlockEntry | TokenType::BlockEnd) =
-                    *self.peek_token()?
-                {
+                if let Token(_, TokenType::BlockEntry | TokenType::BlockEnd) = *self.peek_token()? {
                     self.state = State::BlockSequenceEntry;
-                    Ok((Event::empty_scalar(), mark))
+                    Ok((Event::empty_scalar(), self.empty_span()))
                 } else {
                     self.push_state(State::BlockSequenceEntry);
                     self.parse_node(true, false)
                 }
             }
-            Token(mark, _) => Err(ScanError::new_str(
-                mark,
-                "while parsing a block collection, did not find expected '-' indicator",
+            ref token => Err(unexpected_token(
+                token,
+                ScanErrorKind::ExpectedBlockSequenceEntry,
+                &[TokenKind::BlockEntry, TokenKind::BlockEnd],
             )),
         }
     }
 
-    fn flow_sequence_entry_mapping_key(&mut self) -> ParseResult {
-        if let Token(mark, TokenType::Value | TokenType::FlowEntry | TokenType::FlowSequenceEnd) =
+    fn flow_sequence_entry_mapping_key(&mut self) -> SpannedResult<'input> {
+        if let Token(_, TokenType::Value | TokenType::FlowEntry | TokenType::FlowSequenceEnd) =
             *self.peek_token()?
         {
+            let span = self.empty_span();
             self.skip();
             self.state = State::FlowSequenceEntryMappingValue;
-            Ok((Event::empty_scalar(), mark))
+            Ok((Event::empty_scalar(), span))
         } else {
             self.push_state(State::FlowSequenceEntryMappingValue);
             self.parse_node(false, false)
         }
     }
 
-    fn flow_sequence_entry_mapping_value(&mut self) -> ParseResult {
-        match *self.peek_token()? {
-            Token(_, TokenType::Value) => {
-                self.skip();
-                self.state = State::FlowSequenceEntryMappingValue;
-                if let Token(mark, TokenType::FlowEntry | TokenType::FlowSequenceEnd) =
-                    *self.peek_token()?
-                {
-                    self.state = State::FlowSequenceEntryMappingEnd;
-                    Ok((Event::empty_scalar(), mark))
-                } else {
-                    self.push_state(State::FlowSequenceEntryMappingEnd);
-                    self.parse_node(false, false)
-                }
-            }
-            Token(mark, _) => {
+    fn flow_sequence_entry_mapping_value(&mut self) -> SpannedResult<'input> {
+        if let Token(_, TokenType::Value) = *self.peek_token()? {
+            self.skip();
+            self.state = State::FlowSequenceEntryMappingValue;
+            if let Token(_, TokenType::FlowEntry | TokenType::FlowSequenceEnd) =
+                *self.peek_token()?
+            {
                 self.state = State::FlowSequenceEntryMappingEnd;
-                Ok((Event::empty_scalar(), mark))
+                Ok((Event::empty_scalar(), self.empty_span()))
+            } else {
+                self.push_state(State::FlowSequenceEntryMappingEnd);
+                self.parse_node(false, false)
             }
+        } else {
+            self.state = State::FlowSequenceEntryMappingEnd;
+            Ok((Event::empty_scalar(), self.empty_span()))
         }
     }
 
     #[allow(clippy::unnecessary_wraps)]
-    fn flow_sequence_entry_mapping_end(&mut self) -> ParseResult {
+    fn flow_sequence_entry_mapping_end(&mut self) -> SpannedResult<'input> {
         self.state = State::FlowSequenceEntry;
-        Ok((Event::MappingEnd, self.scanner.mark()))
+        // The end of the pair is reported where the scanner is.
+        self.marker = Some(self.scanner.mark());
+        Ok((Event::MappingEnd, self.empty_span()))
     }
 
     /// Resolve a tag from the handle and the suffix.
@@ -1096,7 +1795,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
             // "!" introduces a local tag. Local tags may have their prefix overridden.
             match self.tags.get("") {
                 Some(prefix) => Ok(Tag {
-                    handle: prefix.to_string(),
+                    handle: prefix.clone(),
                     suffix,
                 }),
                 None => Ok(Tag {
@@ -1109,7 +1808,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
             let prefix = self.tags.get(handle);
             if let Some(prefix) = prefix {
                 Ok(Tag {
-                    handle: prefix.to_string(),
+                    handle: prefix.clone(),
                     suffix,
                 })
             } else {
@@ -1118,7 +1817,10 @@ impl<T: Iterator<Item = char>> Parser<T> {
                 // If the handle is of the form "!foo!", this cannot be a local handle and we need
                 // to error.
                 if handle.len() >= 2 && handle.starts_with('!') && handle.ends_with('!') {
-                    Err(ScanError::new_str(mark, "the handle wasn't declared"))
+                    Err(ScanError::from_kind(
+                        mark,
+                        ScanErrorKind::UndeclaredTagHandle(handle.to_owned()),
+                    ))
                 } else {
                     Ok(Tag {
                         handle: handle.to_string(),
@@ -1130,8 +1832,14 @@ impl<T: Iterator<Item = char>> Parser<T> {
     }
 }
 
-impl<T: Iterator<Item = char>> Iterator for Parser<T> {
-    type Item = Result<(Event, Marker), ScanError>;
+/// Create an error of the given kind about the unexpected `token`, where the parser would have
+/// accepted tokens of the `expected` kinds.
+fn unexpected_token(token: &Token, kind: ScanErrorKind, expected: &[TokenKind]) -> ScanError {
+    ScanError::from_kind(token.0.start, kind).with_expected(token.1.kind(), expected)
+}
+
+impl<'input, T: Input<'input>> Iterator for Parser<'input, T> {
+    type Item = Result<(Event<'input>, Marker), ScanError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.next_event()
@@ -1140,7 +1848,16 @@ impl<T: Iterator<Item = char>> Iterator for Parser<T> {
 
 #[cfg(test)]
 mod test {
-    use super::{Event, Parser};
+    use super::{Event, EventReceiver, Parser, ParserOptions, ScanErrorKind};
+
+    #[test]
+    fn test_fetch_token_without_peek() {
+        let mut parser = Parser::new_from_str("a");
+        let error = parser.fetch_token().unwrap_err();
+        assert_eq!(error.kind(), &ScanErrorKind::InconsistentState);
+        assert!(parser.peek_token().is_ok());
+        assert!(parser.fetch_token().is_ok());
+    }
 
     #[test]
     fn test_peek_eq_parse() {
@@ -1167,6 +1884,33 @@ a5: *x
         }
     }
 
+    #[test]
+    fn test_load_keeps_comments() {
+        struct Sink<'input>(Vec<Event<'input>>);
+        impl<'input> EventReceiver<'input> for Sink<'input> {
+            fn on_event(&mut self, ev: Event<'input>) {
+                self.0.push(ev);
+            }
+        }
+
+        let s = "# a\n- b # c\n# d\n";
+        let mut sink = Sink(vec![]);
+        Parser::new_from_str(s)
+            .options(ParserOptions::default().keep_comments(true))
+            .load(&mut sink, true)
+            .unwrap();
+        let comments: Vec<_> = sink
+            .0
+            .iter()
+            .filter_map(|ev| match ev {
+                Event::Comment(text, _) => Some(&**text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(comments, [" a", " c", " d"]);
+        assert_eq!(sink.0.last(), Some(&Event::StreamEnd));
+    }
+
     #[test]
     fn test_keep_tags_across_multiple_documents() {
         let text = r#"
@@ -1177,15 +1921,38 @@ foo: "bar"
 --- !t!2 &2
 baz: "qux"
 "#;
-        for x in Parser::new_from_str(text).keep_tags(true) {
+        let mut directives = vec![];
+        for x in Parser::new_from_str(text).options(ParserOptions::default().keep_tags(true)) {
             let x = x.unwrap();
-            if let Event::MappingStart(_, tag) = x.0 {
-                let tag = tag.unwrap();
-                assert_eq!(tag.handle, "tag:test,2024:");
+            match x.0 {
+                Event::MappingStart(_, tag, _) => {
+                    let tag = tag.unwrap();
+                    assert_eq!(tag.handle, "tag:test,2024:");
+                }
+                Event::DocumentStart(_, _, declared, inherited, _) => {
+                    directives.push((declared, inherited));
+                }
+                _ => {}
             }
         }
+        let directive = vec![("!t!".to_owned(), "tag:test,2024:".to_owned())];
+        assert_eq!(
+            directives,
+            [(directive.clone(), vec![]), (vec![], directive.clone())]
+        );
+
+        // Directives declared again are not reported as inherited.
+        let redeclared = "%TAG !t! tag:a:\n--- !t!x\n...\n%TAG !t! tag:b:\n--- !t!y\n";
+        let inherited: Vec<_> = Parser::new_from_str(redeclared)
+            .options(ParserOptions::default().keep_tags(true))
+            .filter_map(|x| match x.unwrap().0 {
+                Event::DocumentStart(_, _, _, inherited, _) => Some(inherited),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(inherited, [vec![], vec![]]);
 
-        for x in Parser::new_from_str(text).keep_tags(false) {
+        for x in Parser::new_from_str(text).options(ParserOptions::default().keep_tags(false)) {
             if x.is_err() {
                 // Test successful
                 return;
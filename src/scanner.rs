@@ -9,20 +9,50 @@
 #![allow(clippy::cast_possible_wrap)]
 #![allow(clippy::cast_sign_loss)]
 
-use std::{char, collections::VecDeque, error::Error, fmt};
+use std::{borrow::Cow, char, collections::VecDeque, error::Error, fmt, io, sync::Arc};
 
 use arraydeque::ArrayDeque;
 
-use crate::char_traits::{
-    as_hex, is_alpha, is_anchor_char, is_blank, is_blank_or_breakz, is_break, is_breakz, is_digit,
-    is_flow, is_hex, is_tag_char, is_uri_char, is_z,
+use crate::{
+    char_traits::{
+        as_hex, is_alpha, is_anchor_char, is_blank, is_break, is_digit, is_flow, is_hex,
+        is_tag_char, is_uri_char, is_yaml_1_1_break, is_z,
+    },
+    input::{Input, InputError, ReaderInput},
+    options::{ParserOptions, YamlVersion},
 };
 
-/// The encoding of the input. Currently, only UTF-8 is supported.
+#[cfg(doc)]
+use crate::limits::Limits;
+
+/// The encoding of the input.
+///
+/// Inputs read from bytes have their encoding detected from the first bytes of the stream. See
+/// [`crate::input::ReaderInput`].
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 pub enum TEncoding {
     /// UTF-8 encoding.
     Utf8,
+    /// UTF-16 little-endian encoding.
+    Utf16Le,
+    /// UTF-16 big-endian encoding.
+    Utf16Be,
+    /// UTF-32 little-endian encoding.
+    Utf32Le,
+    /// UTF-32 big-endian encoding.
+    Utf32Be,
+}
+
+impl fmt::Display for TEncoding {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match self {
+            TEncoding::Utf8 => "UTF-8",
+            TEncoding::Utf16Le => "UTF-16LE",
+            TEncoding::Utf16Be => "UTF-16BE",
+            TEncoding::Utf32Le => "UTF-32LE",
+            TEncoding::Utf32Be => "UTF-32BE",
+        })
+    }
 }
 
 /// The style as which the scalar was written in the YAML document.
@@ -36,9 +66,31 @@ pub enum TScalarStyle {
     DoubleQuoted,
 
     /// A YAML literal block (`|` block).
-    Literal,
+    Literal(BlockScalarHeader),
     /// A YAML folded block (`>` block).
-    Folded,
+    Folded(BlockScalarHeader),
+}
+
+/// The details given by the header of a block scalar, and its resulting indentation.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub struct BlockScalarHeader {
+    /// The chomping indicator (`-`, `+` or none).
+    pub chomping: Chomping,
+    /// The explicit indentation indicator (`1` to `9`), if any.
+    ///
+    /// This is relative to the indentation of the parent node.
+    pub indentation_indicator: Option<usize>,
+    /// The indentation of the content lines, in columns from the start of the line.
+    pub indent: usize,
+}
+
+/// The placement of a comment relative to the other contents of its line.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum CommentStyle {
+    /// A comment alone on its line, possibly indented.
+    FullLine,
+    /// A comment following other contents on its line.
+    Trailing,
 }
 
 /// A location in a yaml document.
@@ -46,6 +98,8 @@ pub enum TScalarStyle {
 pub struct Marker {
     /// The index (in chars) in the input string.
     index: usize,
+    /// The offset (in bytes of UTF-8) in the input string.
+    byte_offset: usize,
     /// The line (1-indexed).
     line: usize,
     /// The column (1-indexed).
@@ -54,17 +108,41 @@ pub struct Marker {
 
 impl Marker {
     /// Create a new [`Marker`] at the given position.
+    ///
+    /// The byte offset of the marker is set to `index`, which is only correct if all characters
+    /// before it are ASCII. Use [`Self::with_byte_offset`] to set it otherwise.
     #[must_use]
     pub fn new(index: usize, line: usize, col: usize) -> Marker {
-        Marker { index, line, col }
+        Marker {
+            index,
+            byte_offset: index,
+            line,
+            col,
+        }
     }
 
-    /// Return the index (in bytes) of the marker in the source.
+    /// Return a copy of the marker with the given byte offset.
+    #[must_use]
+    pub fn with_byte_offset(mut self, byte_offset: usize) -> Marker {
+        self.byte_offset = byte_offset;
+        self
+    }
+
+    /// Return the index (in chars) of the marker in the source.
     #[must_use]
     pub fn index(&self) -> usize {
         self.index
     }
 
+    /// Return the offset (in bytes) of the marker in the UTF-8 source.
+    ///
+    /// When parsing from a `&str`, this can be used to slice it. When reading from bytes in
+    /// another encoding, this is the offset the marker would have if the input were UTF-8.
+    #[must_use]
+    pub fn byte_offset(&self) -> usize {
+        self.byte_offset
+    }
+
     /// Return the line of the marker in the source.
     #[must_use]
     pub fn line(&self) -> usize {
@@ -78,47 +156,593 @@ impl Marker {
     }
 }
 
-/// An error that occurred while scanning.
+/// A range of locations in a YAML document.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Default)]
+pub struct Span {
+    /// The start (inclusive) of the range.
+    pub start: Marker,
+    /// The end (exclusive) of the range.
+    pub end: Marker,
+}
+
+impl Span {
+    /// Create a new [`Span`] for the given range.
+    #[must_use]
+    pub fn new(start: Marker, end: Marker) -> Span {
+        Span { start, end }
+    }
+
+    /// Create a empty [`Span`] at a given location.
+    ///
+    /// Such spans are used for tokens and events which do not correspond to any text in the
+    /// source, like the end of a block collection.
+    #[must_use]
+    pub fn empty(mark: Marker) -> Span {
+        Span {
+            start: mark,
+            end: mark,
+        }
+    }
+
+    /// Return whether the span covers no text.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.start.index == self.end.index
+    }
+}
+
+/// The kind of a [`ScanError`].
+///
+/// Variants carry the offending piece of input where relevant. The human-readable description of
+/// the error is available through [`ScanError::info`].
 #[derive(Clone, PartialEq, Debug, Eq)]
+#[non_exhaustive]
+pub enum ScanErrorKind {
+    /// An error created with [`ScanError::new`] or [`ScanError::new_str`].
+    Custom,
+    /// The underlying reader returned an error of the given kind.
+    ///
+    /// The error itself is the [`Error::source`] of the [`ScanError`].
+    Io(io::ErrorKind),
+    /// The input contains a byte sequence that is not valid in its encoding.
+    InvalidSequence(TEncoding),
+    /// The stream ended in the middle of a document.
+    UnexpectedEof,
+    /// A character that cannot start any token was found.
+    UnexpectedCharacter(char),
+    /// A byte order mark was found in the middle of a document.
+    MisplacedByteOrderMark,
+    /// The scanner could not find the next token.
+    NoNextToken,
+    /// Content was found on the same line as a `...` document end marker.
+    ContentAfterDocumentEnd,
+    /// A token is not indented correctly.
+    InvalidIndentation,
+    /// A tab was used for indentation of a block collection.
+    TabsInBlockIndentation,
+    /// A tab was found where tabs are not allowed.
+    TabsNotAllowed,
+    /// A token was not followed by whitespace.
+    ExpectedWhitespace,
+    /// A comment was not preceded by whitespace.
+    CommentWithoutWhitespace,
+    /// A multi-line implicit key was not followed by `:`.
+    SimpleKeyExpectedColon,
+    /// A required implicit key could not be found.
+    SimpleKeyExpected,
+    /// The nesting of collections is too deep.
+    RecursionLimitExceeded,
+    /// A directive was followed by something else than a comment or line break.
+    DirectiveTrailingContent,
+    /// A `%` was not followed by a directive name.
+    DirectiveNameMissing,
+    /// A directive name contains a non-alphabetical character.
+    DirectiveNameInvalidCharacter(char),
+    /// The version of a `%YAML` directive is missing.
+    VersionNumberMissing,
+    /// A component of the version of a `%YAML` directive is too long.
+    VersionNumberTooLong,
+    /// The components of the version of a `%YAML` directive are not separated by `.`.
+    VersionExpectedDigitOrDot,
+    /// A `%TAG` directive was followed by something else than a comment or line break.
+    TagDirectiveTrailingContent,
+    /// The handle of a `%TAG` directive does not start with `!`.
+    TagDirectiveExpectedBang,
+    /// A tag was not followed by whitespace or a line break.
+    TagTrailingContent,
+    /// A tag handle does not end with `!`.
+    TagHandleExpectedBang,
+    /// The prefix of a `%TAG` directive starts with an invalid character.
+    InvalidGlobalTagCharacter(char),
+    /// A verbatim tag (`!<...>`) is missing its closing `>`.
+    UnterminatedVerbatimTag,
+    /// A tag has no URI.
+    TagUriMissing,
+    /// A `%` in a tag URI is not followed by two hexadecimal digits.
+    InvalidUriEscape,
+    /// A URI escape sequence starts with an invalid UTF-8 octet.
+    InvalidUriLeadingOctet(u32),
+    /// A URI escape sequence continues with an invalid UTF-8 octet.
+    InvalidUriTrailingOctet(u32),
+    /// A URI escape sequence does not decode to a valid codepoint.
+    InvalidUriCodepoint(u32),
+    /// An anchor or alias has no name.
+    AnchorNameMissing,
+    /// An anchor is not indented correctly.
+    InvalidAnchorIndentation,
+    /// A block sequence entry (`-`) was found inside a flow collection.
+    BlockEntryInFlow,
+    /// A block sequence entry (`-`) was found where it is not allowed.
+    BlockEntryNotAllowed,
+    /// A block sequence entry (`-`) was not followed by whitespace.
+    BlockEntryExpectedWhitespace,
+    /// The indentation indicator of a block scalar is `0`.
+    BlockScalarZeroIndentation,
+    /// The header of a block scalar was followed by something else than a comment or line break.
+    BlockScalarTrailingContent,
+    /// The content of a block scalar starts with a tab.
+    BlockScalarStartsWithTab,
+    /// A line of a block scalar is less indented than the block scalar.
+    BlockScalarIndentation,
+    /// A document marker was found inside a quoted scalar.
+    QuotedScalarDocumentIndicator,
+    /// The stream ended inside a quoted scalar.
+    UnterminatedQuotedScalar,
+    /// A line of a quoted scalar is not indented correctly.
+    QuotedScalarIndentation,
+    /// A line of a quoted scalar is indented with a tab.
+    QuotedScalarTabIndentation,
+    /// A double-quoted scalar is followed by invalid content on the same line.
+    QuotedScalarTrailingContent,
+    /// An escape sequence in a double-quoted scalar uses an unknown escape character.
+    UnknownEscape(char),
+    /// An escape sequence in a double-quoted scalar does not have enough hexadecimal digits.
+    InvalidHexEscape,
+    /// An escape sequence in a double-quoted scalar is not a valid codepoint.
+    InvalidUnicodeEscape(u32),
+    /// A line of a flow collection is not indented correctly.
+    FlowIndentation,
+    /// A plain scalar starts with `-` followed by a flow indicator.
+    PlainScalarDashFlowIndicator,
+    /// A tab was found inside a plain scalar where it is not allowed.
+    PlainScalarTab,
+    /// A mapping key (`?`) was found where it is not allowed.
+    MappingKeyNotAllowed,
+    /// A mapping value (`:`) was found where it is not allowed.
+    MappingValueNotAllowed,
+    /// A `:` directly precedes the start of a flow collection in a flow mapping.
+    ColonBeforeFlowCollection,
+    /// A `:` was not followed by whitespace.
+    ColonExpectedWhitespace,
+    /// A `:` was found where it cannot be placed.
+    IllegalColonPlacement,
+    /// The stream does not start with a stream start token.
+    ExpectedStreamStart,
+    /// A document does not start with a document start token.
+    ExpectedDocumentStart,
+    /// A document has more than one `%YAML` directive.
+    DuplicateVersionDirective,
+    /// A document has more than one `%TAG` directive for the given handle.
+    DuplicateTagDirective(String),
+    /// A directive follows a document that was not ended with `...`.
+    MissingDocumentEnd,
+    /// An alias refers to the given anchor name, which was not defined.
+    UnknownAnchor(String),
+    /// A node has no content.
+    ExpectedNodeContent,
+    /// An entry of a block mapping is not a key.
+    ExpectedBlockMappingKey,
+    /// An entry of a block sequence does not start with `-`.
+    ExpectedBlockSequenceEntry,
+    /// An entry of a flow mapping is not followed by `,` or `}`.
+    ExpectedFlowMappingSeparator,
+    /// An entry of a flow sequence is not followed by `,` or `]`.
+    ExpectedFlowSequenceSeparator,
+    /// A tag uses the given named handle, which was not declared with a `%TAG` directive.
+    UndeclaredTagHandle(String),
+    /// Collections are nested deeper than [`Limits::max_depth`].
+    DepthLimitExceeded,
+    /// A scalar is longer than [`Limits::max_scalar_bytes`].
+    ScalarLimitExceeded,
+    /// The stream has more events than [`Limits::max_events`].
+    EventLimitExceeded,
+    /// The stream has more documents than [`Limits::max_documents`].
+    DocumentLimitExceeded,
+    /// The stream defines more anchors than [`Limits::max_anchors`].
+    AnchorLimitExceeded,
+    /// The stream has more aliases than [`Limits::max_aliases`].
+    AliasLimitExceeded,
+    /// The stream has more nodes than [`Limits::max_expanded_nodes`] once aliases are expanded.
+    ExpandedNodeLimitExceeded,
+    /// Expanding aliases multiplies the number of nodes by more than
+    /// [`Limits::max_expansion_ratio`].
+    ExpansionRatioExceeded,
+    /// A document does not end with a document end event.
+    ExpectedDocumentEnd,
+    /// The scanner or the parser reached a state it should never be in.
+    ///
+    /// This is a bug, reported as an error rather than a panic.
+    InconsistentState,
+}
+
+impl ScanErrorKind {
+    /// Whether the parser cannot recover from the error.
+    ///
+    /// This is the case when exceeding one of the [`Limits`] or in an inconsistent state.
+    pub(crate) fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            ScanErrorKind::InconsistentState
+                | ScanErrorKind::DepthLimitExceeded
+                | ScanErrorKind::ScalarLimitExceeded
+                | ScanErrorKind::EventLimitExceeded
+                | ScanErrorKind::DocumentLimitExceeded
+                | ScanErrorKind::AnchorLimitExceeded
+                | ScanErrorKind::AliasLimitExceeded
+                | ScanErrorKind::ExpandedNodeLimitExceeded
+                | ScanErrorKind::ExpansionRatioExceeded
+        )
+    }
+}
+
+impl fmt::Display for ScanErrorKind {
+    #[allow(clippy::too_many_lines)]
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let info = match self {
+            ScanErrorKind::Custom => "error",
+            ScanErrorKind::Io(kind) => {
+                return write!(formatter, "error while reading input: {kind}")
+            }
+            ScanErrorKind::InvalidSequence(encoding) => {
+                return write!(formatter, "invalid {encoding} sequence in input")
+            }
+            ScanErrorKind::UnexpectedEof => "unexpected eof",
+            ScanErrorKind::UnexpectedCharacter(c) => {
+                return write!(formatter, "unexpected character: `{c}'")
+            }
+            ScanErrorKind::MisplacedByteOrderMark => {
+                "a byte order mark may only appear at the start of a document"
+            }
+            ScanErrorKind::NoNextToken => "did not find expected next token",
+            ScanErrorKind::ContentAfterDocumentEnd => "invalid content after document end marker",
+            ScanErrorKind::InvalidIndentation => "invalid indentation",
+            ScanErrorKind::TabsInBlockIndentation => {
+                "tabs disallowed within this context (block indentation)"
+            }
+            ScanErrorKind::TabsNotAllowed => "tabs disallowed in this context",
+            ScanErrorKind::ExpectedWhitespace => "expected whitespace",
+            ScanErrorKind::CommentWithoutWhitespace => {
+                "comments must be separated from other tokens by whitespace"
+            }
+            ScanErrorKind::SimpleKeyExpectedColon => "simple key expect ':'",
+            ScanErrorKind::SimpleKeyExpected => "simple key expected",
+            ScanErrorKind::RecursionLimitExceeded => "recursion limit exceeded",
+            ScanErrorKind::DirectiveTrailingContent => {
+                "while scanning a directive, did not find expected comment or line break"
+            }
+            ScanErrorKind::DirectiveNameMissing => {
+                "while scanning a directive, could not find expected directive name"
+            }
+            ScanErrorKind::DirectiveNameInvalidCharacter(_) => {
+                "while scanning a directive, found unexpected non-alphabetical character"
+            }
+            ScanErrorKind::VersionNumberMissing => {
+                "while scanning a YAML directive, did not find expected version number"
+            }
+            ScanErrorKind::VersionNumberTooLong => {
+                "while scanning a YAML directive, found extremely long version number"
+            }
+            ScanErrorKind::VersionExpectedDigitOrDot => {
+                "while scanning a YAML directive, did not find expected digit or '.' character"
+            }
+            ScanErrorKind::TagDirectiveTrailingContent => {
+                "while scanning TAG, did not find expected whitespace or line break"
+            }
+            ScanErrorKind::TagDirectiveExpectedBang => {
+                "while parsing a tag directive, did not find expected '!'"
+            }
+            ScanErrorKind::TagTrailingContent => {
+                "while scanning a tag, did not find expected whitespace or line break"
+            }
+            ScanErrorKind::TagHandleExpectedBang => "while scanning a tag, did not find expected '!'",
+            ScanErrorKind::InvalidGlobalTagCharacter(_) => "invalid global tag character",
+            ScanErrorKind::UnterminatedVerbatimTag => {
+                "while scanning a verbatim tag, did not find the expected '>'"
+            }
+            ScanErrorKind::TagUriMissing => "while parsing a tag, did not find expected tag URI",
+            ScanErrorKind::InvalidUriEscape => {
+                "while parsing a tag, did not find URI escaped octet"
+            }
+            ScanErrorKind::InvalidUriLeadingOctet(_) => {
+                "while parsing a tag, found an incorrect leading UTF-8 octet"
+            }
+            ScanErrorKind::InvalidUriTrailingOctet(_) => {
+                "while parsing a tag, found an incorrect trailing UTF-8 octet"
+            }
+            ScanErrorKind::InvalidUriCodepoint(_) => {
+                "while parsing a tag, found an invalid UTF-8 codepoint"
+            }
+            ScanErrorKind::AnchorNameMissing => {
+                "while scanning an anchor or alias, did not find expected alphabetic or numeric character"
+            }
+            ScanErrorKind::InvalidAnchorIndentation => "invalid indentation for anchor",
+            ScanErrorKind::BlockEntryInFlow => r#""-" is only valid inside a block"#,
+            ScanErrorKind::BlockEntryNotAllowed => {
+                "block sequence entries are not allowed in this context"
+            }
+            ScanErrorKind::BlockEntryExpectedWhitespace => {
+                "'-' must be followed by a valid YAML whitespace"
+            }
+            ScanErrorKind::BlockScalarZeroIndentation => {
+                "while scanning a block scalar, found an indentation indicator equal to 0"
+            }
+            ScanErrorKind::BlockScalarTrailingContent => {
+                "while scanning a block scalar, did not find expected comment or line break"
+            }
+            ScanErrorKind::BlockScalarStartsWithTab => {
+                "a block scalar content cannot start with a tab"
+            }
+            ScanErrorKind::BlockScalarIndentation => "wrongly indented line in block scalar",
+            ScanErrorKind::QuotedScalarDocumentIndicator => {
+                "while scanning a quoted scalar, found unexpected document indicator"
+            }
+            ScanErrorKind::UnterminatedQuotedScalar => {
+                "while scanning a quoted scalar, found unexpected end of stream"
+            }
+            ScanErrorKind::QuotedScalarIndentation => "invalid indentation in quoted scalar",
+            ScanErrorKind::QuotedScalarTabIndentation => "tab cannot be used as indentation",
+            ScanErrorKind::QuotedScalarTrailingContent => {
+                "invalid trailing content after double-quoted scalar"
+            }
+            ScanErrorKind::UnknownEscape(_) => {
+                "while parsing a quoted scalar, found unknown escape character"
+            }
+            ScanErrorKind::InvalidHexEscape => {
+                "while parsing a quoted scalar, did not find expected hexadecimal number"
+            }
+            ScanErrorKind::InvalidUnicodeEscape(_) => {
+                "while parsing a quoted scalar, found invalid Unicode character escape code"
+            }
+            ScanErrorKind::FlowIndentation => "invalid indentation in flow construct",
+            ScanErrorKind::PlainScalarDashFlowIndicator => {
+                "plain scalar cannot start with '-' followed by ,[]{}"
+            }
+            ScanErrorKind::PlainScalarTab => "while scanning a plain scalar, found a tab",
+            ScanErrorKind::MappingKeyNotAllowed => "mapping keys are not allowed in this context",
+            ScanErrorKind::MappingValueNotAllowed => {
+                "mapping values are not allowed in this context"
+            }
+            ScanErrorKind::ColonBeforeFlowCollection => {
+                "':' may not precede any of `[{` in flow mapping"
+            }
+            ScanErrorKind::ColonExpectedWhitespace => {
+                "':' must be followed by a valid YAML whitespace"
+            }
+            ScanErrorKind::IllegalColonPlacement => "illegal placement of ':' indicator",
+            ScanErrorKind::ExpectedStreamStart => "did not find expected <stream-start>",
+            ScanErrorKind::ExpectedDocumentStart => "did not find expected <document-start>",
+            ScanErrorKind::DuplicateVersionDirective => "duplicate version directive",
+            ScanErrorKind::DuplicateTagDirective(_) => {
+                "the TAG directive must only be given at most once per handle in the same document"
+            }
+            ScanErrorKind::MissingDocumentEnd => {
+                "missing explicit document end marker before directive"
+            }
+            ScanErrorKind::UnknownAnchor(_) => "while parsing node, found unknown anchor",
+            ScanErrorKind::ExpectedNodeContent => {
+                "while parsing a node, did not find expected node content"
+            }
+            ScanErrorKind::ExpectedBlockMappingKey => {
+                "while parsing a block mapping, did not find expected key"
+            }
+            ScanErrorKind::ExpectedBlockSequenceEntry => {
+                "while parsing a block collection, did not find expected '-' indicator"
+            }
+            ScanErrorKind::ExpectedFlowMappingSeparator => {
+                "while parsing a flow mapping, did not find expected ',' or '}'"
+            }
+            ScanErrorKind::ExpectedFlowSequenceSeparator => {
+                "while parsing a flow sequence, expected ',' or ']'"
+            }
+            ScanErrorKind::UndeclaredTagHandle(_) => "the handle wasn't declared",
+            ScanErrorKind::DepthLimitExceeded => "nesting depth limit exceeded",
+            ScanErrorKind::ScalarLimitExceeded => "scalar length limit exceeded",
+            ScanErrorKind::EventLimitExceeded => "event limit exceeded",
+            ScanErrorKind::DocumentLimitExceeded => "document limit exceeded",
+            ScanErrorKind::AnchorLimitExceeded => "anchor limit exceeded",
+            ScanErrorKind::AliasLimitExceeded => "alias limit exceeded",
+            ScanErrorKind::ExpandedNodeLimitExceeded => "expanded node limit exceeded",
+            ScanErrorKind::ExpansionRatioExceeded => "alias expansion ratio exceeded",
+            ScanErrorKind::ExpectedDocumentEnd => "did not find expected <document-end>",
+            ScanErrorKind::InconsistentState => "internal error: inconsistent parser state",
+        };
+        formatter.write_str(info)
+    }
+}
+
+/// A construct which was being parsed when a [`ScanError`] occurred.
+///
+/// See [`ScanError::context`].
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+#[non_exhaustive]
+pub enum ErrorContext {
+    /// A document.
+    Document,
+    /// A block mapping.
+    BlockMapping,
+    /// A block sequence, indented or not.
+    BlockSequence,
+    /// A flow mapping, or a single-pair mapping in a flow sequence.
+    FlowMapping,
+    /// A flow sequence.
+    FlowSequence,
+    /// A single-quoted or double-quoted scalar.
+    QuotedScalar,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match self {
+            ErrorContext::Document => "document",
+            ErrorContext::BlockMapping => "block mapping",
+            ErrorContext::BlockSequence => "block sequence",
+            ErrorContext::FlowMapping => "flow mapping",
+            ErrorContext::FlowSequence => "flow sequence",
+            ErrorContext::QuotedScalar => "quoted scalar",
+        })
+    }
+}
+
+/// An error that occurred while scanning.
+#[derive(Clone, Debug)]
 pub struct ScanError {
     /// The position at which the error happened in the source.
     mark: Marker,
+    /// The kind of error.
+    kind: ScanErrorKind,
     /// Human-readable details about the error.
     info: String,
+    /// The constructs the error happened in, innermost first, and where they started.
+    context: Vec<(ErrorContext, Marker)>,
+    /// Details only some errors have.
+    ///
+    /// Boxed to keep errors small.
+    details: Option<Box<ErrorDetails>>,
 }
 
+/// Details of a [`ScanError`] that only some errors have.
+#[derive(Clone, Debug)]
+enum ErrorDetails {
+    /// The kind of the unexpected token the parser found and the kinds of tokens it would have
+    /// accepted instead.
+    Unexpected(TokenKind, Vec<TokenKind>),
+    /// The error returned by the reader, for [`ScanErrorKind::Io`] errors.
+    ///
+    /// Shared so that errors can be cloned. It is available through [`Error::source`].
+    Io(Arc<io::Error>),
+}
+
+impl PartialEq for ScanError {
+    /// Compare errors, ignoring the I/O error they hold, if any.
+    ///
+    /// [`io::Error`]s cannot be compared. Their kind and description are already part of the
+    /// [`ScanErrorKind`] and the information string of the error.
+    fn eq(&self, other: &Self) -> bool {
+        self.mark == other.mark
+            && self.kind == other.kind
+            && self.info == other.info
+            && self.context == other.context
+            && self.found() == other.found()
+            && self.expected() == other.expected()
+    }
+}
+
+impl Eq for ScanError {}
+
 impl ScanError {
     /// Create a new error from a location and an error string.
+    ///
+    /// The kind of the error is [`ScanErrorKind::Custom`].
     #[must_use]
     pub fn new(loc: Marker, info: String) -> ScanError {
-        ScanError { mark: loc, info }
+        ScanError {
+            mark: loc,
+            kind: ScanErrorKind::Custom,
+            info,
+            context: Vec::new(),
+            details: None,
+        }
     }
 
     /// Convenience alias for string slices.
     #[must_use]
     pub fn new_str(loc: Marker, info: &str) -> ScanError {
+        ScanError::new(loc, info.to_owned())
+    }
+
+    /// Create a new error from a location and a kind, described by the kind's `Display`.
+    #[must_use]
+    pub fn from_kind(loc: Marker, kind: ScanErrorKind) -> ScanError {
         ScanError {
             mark: loc,
-            info: info.to_owned(),
+            info: kind.to_string(),
+            kind,
+            context: Vec::new(),
+            details: None,
         }
     }
 
+    /// Record that the error happened in `context`, which started at `start`.
+    ///
+    /// Contexts are added from the innermost outwards.
+    #[must_use]
+    pub fn with_context(mut self, context: ErrorContext, start: Marker) -> ScanError {
+        self.context.push((context, start));
+        self
+    }
+
     /// Return the marker pointing to the error in the source.
     #[must_use]
     pub fn marker(&self) -> &Marker {
         &self.mark
     }
 
+    /// Return the kind of error that happened.
+    #[must_use]
+    pub fn kind(&self) -> &ScanErrorKind {
+        &self.kind
+    }
+
     /// Return the information string describing the error that happened.
     #[must_use]
     pub fn info(&self) -> &str {
         self.info.as_ref()
     }
+
+    /// Record that the parser found a token of kind `found` where it accepts only tokens of the
+    /// `expected` kinds.
+    #[must_use]
+    pub fn with_expected(mut self, found: TokenKind, expected: &[TokenKind]) -> ScanError {
+        self.details = Some(Box::new(ErrorDetails::Unexpected(found, expected.to_vec())));
+        self
+    }
+
+    /// Return the kind of the unexpected token the parser found, if the error is about one.
+    #[must_use]
+    pub fn found(&self) -> Option<TokenKind> {
+        match self.details.as_deref() {
+            Some(ErrorDetails::Unexpected(found, _)) => Some(*found),
+            _ => None,
+        }
+    }
+
+    /// Return the kinds of tokens the parser would have accepted instead of [`Self::found`].
+    ///
+    /// This is empty if the error is not about an unexpected token.
+    #[must_use]
+    pub fn expected(&self) -> &[TokenKind] {
+        match self.details.as_deref() {
+            Some(ErrorDetails::Unexpected(_, expected)) => expected,
+            _ => &[],
+        }
+    }
+
+    /// Return the constructs the error happened in, innermost first, and where they started.
+    ///
+    /// This helps locating the cause of errors which are only detected far from it, such as an
+    /// unclosed flow sequence or an unterminated quoted scalar.
+    #[must_use]
+    pub fn context(&self) -> &[(ErrorContext, Marker)] {
+        &self.context
+    }
 }
 
 impl Error for ScanError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        None
+        match self.details.as_deref() {
+            Some(ErrorDetails::Io(e)) => Some(&**e),
+            _ => None,
+        }
     }
 }
 
@@ -128,16 +752,108 @@ impl fmt::Display for ScanError {
             formatter,
             "{} at byte {} line {} column {}",
             self.info,
-            self.mark.index,
+            self.mark.byte_offset,
             self.mark.line,
             self.mark.col + 1,
         )
     }
 }
 
+/// The kind of a [`Warning`].
+#[derive(Clone, PartialEq, Debug, Eq)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// A directive other than `%YAML` and `%TAG`, holding its name. The directive is ignored.
+    UnknownDirective(String),
+    /// An anchor defined again in the same document, holding its name. Aliases refer to the
+    /// latest definition.
+    RedefinedAnchor(String),
+    /// A `%YAML` directive with a minor version above 1.2 (major, minor). The document is parsed
+    /// as YAML 1.2.
+    HigherMinorVersion(u32, u32),
+    /// A tab in indentation, accepted in [lenient](ParserOptions::lenient) mode. It
+    /// counts as a single column.
+    TabIndentation,
+    /// A comment not separated from the preceding token by whitespace, accepted in
+    /// [lenient](ParserOptions::lenient) mode.
+    CommentWithoutWhitespace,
+    /// A plain scalar starting with a reserved indicator (`@` or `` ` ``), holding it, accepted
+    /// in [lenient](ParserOptions::lenient) mode. The indicator is part of the scalar.
+    ReservedIndicator(char),
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WarningKind::UnknownDirective(name) => {
+                write!(formatter, "found unknown directive '%{name}'")
+            }
+            WarningKind::RedefinedAnchor(name) => {
+                write!(formatter, "found redefined anchor '&{name}'")
+            }
+            WarningKind::HigherMinorVersion(major, minor) => write!(
+                formatter,
+                "found YAML version {major}.{minor}, parsing as YAML 1.2"
+            ),
+            WarningKind::TabIndentation => write!(formatter, "found a tab used as indentation"),
+            WarningKind::CommentWithoutWhitespace => write!(
+                formatter,
+                "found a comment not separated from other tokens by whitespace"
+            ),
+            WarningKind::ReservedIndicator(c) => write!(
+                formatter,
+                "found reserved indicator '{c}' at the start of a plain scalar"
+            ),
+        }
+    }
+}
+
+/// A non-fatal issue found while parsing, reported by [`Parser::warnings`](crate::Parser::warnings)
+/// and [`Scanner::take_warnings`].
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Warning {
+    /// The position at which the issue was found in the source.
+    mark: Marker,
+    /// The kind of issue.
+    kind: WarningKind,
+}
+
+impl Warning {
+    /// Create a new warning from a location and a kind.
+    #[must_use]
+    pub fn new(mark: Marker, kind: WarningKind) -> Warning {
+        Warning { mark, kind }
+    }
+
+    /// Return the marker pointing to the issue in the source.
+    #[must_use]
+    pub fn marker(&self) -> &Marker {
+        &self.mark
+    }
+
+    /// Return the kind of issue that was found.
+    #[must_use]
+    pub fn kind(&self) -> &WarningKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} at byte {} line {} column {}",
+            self.kind,
+            self.mark.byte_offset(),
+            self.mark.line(),
+            self.mark.col() + 1,
+        )
+    }
+}
+
 /// The contents of a scanner token.
 #[derive(Clone, PartialEq, Debug, Eq)]
-pub enum TokenType {
+pub enum TokenType<'input> {
     /// The start of the stream. Sent first, before even [`TokenType::DocumentStart`].
     StreamStart(TEncoding),
     /// The end of the stream, EOF.
@@ -156,6 +872,10 @@ pub enum TokenType {
         /// Prefix
         String,
     ),
+    /// A directive other than `%YAML` and `%TAG`, reserved for future use, holding its name.
+    ///
+    /// Its parameters are skipped.
+    ReservedDirective(String),
     /// The start of a YAML document (`---`).
     DocumentStart,
     /// The end of a YAML document (`...`).
@@ -187,9 +907,9 @@ pub enum TokenType {
     /// A value in a mapping.
     Value,
     /// A reference to an anchor.
-    Alias(String),
+    Alias(Cow<'input, str>),
     /// A YAML anchor (`&`/`*`).
-    Anchor(String),
+    Anchor(Cow<'input, str>),
     /// A YAML tag (starting with bangs `!`).
     Tag(
         /// The handle of the tag.
@@ -198,12 +918,140 @@ pub enum TokenType {
         String,
     ),
     /// A regular YAML scalar.
-    Scalar(TScalarStyle, String),
+    Scalar(TScalarStyle, Cow<'input, str>),
+}
+
+impl TokenType<'_> {
+    /// Return the kind of the token, without its contents.
+    #[must_use]
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            TokenType::StreamStart(_) => TokenKind::StreamStart,
+            TokenType::StreamEnd => TokenKind::StreamEnd,
+            TokenType::VersionDirective(..) => TokenKind::VersionDirective,
+            TokenType::TagDirective(..) => TokenKind::TagDirective,
+            TokenType::ReservedDirective(_) => TokenKind::ReservedDirective,
+            TokenType::DocumentStart => TokenKind::DocumentStart,
+            TokenType::DocumentEnd => TokenKind::DocumentEnd,
+            TokenType::BlockSequenceStart => TokenKind::BlockSequenceStart,
+            TokenType::BlockMappingStart => TokenKind::BlockMappingStart,
+            TokenType::BlockEnd => TokenKind::BlockEnd,
+            TokenType::FlowSequenceStart => TokenKind::FlowSequenceStart,
+            TokenType::FlowSequenceEnd => TokenKind::FlowSequenceEnd,
+            TokenType::FlowMappingStart => TokenKind::FlowMappingStart,
+            TokenType::FlowMappingEnd => TokenKind::FlowMappingEnd,
+            TokenType::BlockEntry => TokenKind::BlockEntry,
+            TokenType::FlowEntry => TokenKind::FlowEntry,
+            TokenType::Key => TokenKind::Key,
+            TokenType::Value => TokenKind::Value,
+            TokenType::Alias(_) => TokenKind::Alias,
+            TokenType::Anchor(_) => TokenKind::Anchor,
+            TokenType::Tag(..) => TokenKind::Tag,
+            TokenType::Scalar(..) => TokenKind::Scalar,
+        }
+    }
+}
+
+/// The kind of a [`TokenType`], without its contents.
+///
+/// Parser errors about an unexpected token report the kinds of tokens that would have been
+/// accepted instead (see [`ScanError::expected`]).
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Hash)]
+#[non_exhaustive]
+pub enum TokenKind {
+    /// See [`TokenType::StreamStart`].
+    StreamStart,
+    /// See [`TokenType::StreamEnd`].
+    StreamEnd,
+    /// See [`TokenType::VersionDirective`].
+    VersionDirective,
+    /// See [`TokenType::TagDirective`].
+    TagDirective,
+    /// See [`TokenType::ReservedDirective`].
+    ReservedDirective,
+    /// See [`TokenType::DocumentStart`].
+    DocumentStart,
+    /// See [`TokenType::DocumentEnd`].
+    DocumentEnd,
+    /// See [`TokenType::BlockSequenceStart`].
+    BlockSequenceStart,
+    /// See [`TokenType::BlockMappingStart`].
+    BlockMappingStart,
+    /// See [`TokenType::BlockEnd`].
+    BlockEnd,
+    /// See [`TokenType::FlowSequenceStart`].
+    FlowSequenceStart,
+    /// See [`TokenType::FlowSequenceEnd`].
+    FlowSequenceEnd,
+    /// See [`TokenType::FlowMappingStart`].
+    FlowMappingStart,
+    /// See [`TokenType::FlowMappingEnd`].
+    FlowMappingEnd,
+    /// See [`TokenType::BlockEntry`].
+    BlockEntry,
+    /// See [`TokenType::FlowEntry`].
+    FlowEntry,
+    /// See [`TokenType::Key`].
+    Key,
+    /// See [`TokenType::Value`].
+    Value,
+    /// See [`TokenType::Alias`].
+    Alias,
+    /// See [`TokenType::Anchor`].
+    Anchor,
+    /// See [`TokenType::Tag`].
+    Tag,
+    /// See [`TokenType::Scalar`].
+    Scalar,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match self {
+            TokenKind::StreamStart => "the start of the stream",
+            TokenKind::StreamEnd => "the end of the stream",
+            TokenKind::VersionDirective => "a %YAML directive",
+            TokenKind::TagDirective => "a %TAG directive",
+            TokenKind::ReservedDirective => "a directive",
+            TokenKind::DocumentStart => "'---'",
+            TokenKind::DocumentEnd => "'...'",
+            TokenKind::BlockSequenceStart => "a block sequence",
+            TokenKind::BlockMappingStart => "a block mapping",
+            TokenKind::BlockEnd => "the end of a block collection",
+            TokenKind::FlowSequenceStart => "'['",
+            TokenKind::FlowSequenceEnd => "']'",
+            TokenKind::FlowMappingStart => "'{'",
+            TokenKind::FlowMappingEnd => "'}'",
+            TokenKind::BlockEntry => "'-'",
+            TokenKind::FlowEntry => "','",
+            TokenKind::Key => "a mapping key",
+            TokenKind::Value => "':'",
+            TokenKind::Alias => "an alias",
+            TokenKind::Anchor => "an anchor",
+            TokenKind::Tag => "a tag",
+            TokenKind::Scalar => "a scalar",
+        })
+    }
 }
 
 /// A scanner token.
+///
+/// The [`Span`] covers the text of the token in the source. Tokens which the scanner inserts
+/// without them appearing in the source (e.g.: [`TokenType::BlockMappingStart`]) have an empty
+/// span.
 #[derive(Clone, PartialEq, Debug, Eq)]
-pub struct Token(pub Marker, pub TokenType);
+pub struct Token<'input>(pub Span, pub TokenType<'input>);
+
+/// A comment, recorded by the scanner if [`ParserOptions::keep_comments`] is set.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Comment<'input> {
+    /// The span of the comment, from the `#` to the end of the line.
+    pub span: Span,
+    /// The text of the comment, without the leading `#`.
+    pub text: Cow<'input, str>,
+    /// Whether the comment is alone on its line.
+    pub style: CommentStyle,
+}
 
 /// A scalar that was parsed and may correspond to a simple key.
 ///
@@ -366,7 +1214,7 @@ const BUFFER_LEN: usize = 16;
 /// YAML documents.
 #[derive(Debug)]
 #[allow(clippy::struct_excessive_bools)]
-pub struct Scanner<T> {
+pub struct Scanner<'input, T> {
     /// The reader, providing with characters.
     rdr: T,
     /// The position of the cursor within the reader.
@@ -377,11 +1225,22 @@ pub struct Scanner<T> {
     /// instance, if we just read a scalar, it can be a value or a key if an implicit mapping
     /// follows. In this case, the token stays in the `VecDeque` but cannot be returned from
     /// [`Self::next`] until we have more context.
-    tokens: VecDeque<Token>,
+    ///
+    /// Each token is queued along with the marker at which the parser reports it, see
+    /// [`Self::token_marker`].
+    tokens: VecDeque<(Token<'input>, Marker)>,
+    /// The marker at which the parser reports the last token returned by [`Self::next_token`].
+    token_marker: Marker,
     /// Buffer for the next characters to consume.
     buffer: ArrayDeque<char, BUFFER_LEN>,
     /// The last error that happened.
     error: Option<ScanError>,
+    /// The first error returned by [`Self::rdr`], if any.
+    ///
+    /// Upon an input error, a `\0` is pushed into [`Self::buffer`] as if the input had ended. The
+    /// error is reported when the scanner reaches that `\0`, so that its marker points to where
+    /// the input failed.
+    input_error: Option<InputError>,
 
     /// Whether we have already emitted the `StreamStart` token.
     stream_start_produced: bool,
@@ -405,12 +1264,18 @@ pub struct Scanner<T> {
     indents: Vec<Indent>,
     /// Level of nesting of flow sequences.
     flow_level: u8,
+    /// The flow collections we are in and where they started, to give context to errors.
+    flow_starts: Vec<(ErrorContext, Marker)>,
     /// The number of tokens that have been returned from the scanner.
     ///
     /// This excludes the tokens from [`Self::tokens`].
     tokens_parsed: usize,
     /// Whether a token is ready to be taken from [`Self::tokens`].
     token_available: bool,
+    /// Whether we are in a document prefix, where a byte order mark may appear.
+    ///
+    /// This is the case at the start of the stream and after a document end marker (`...`).
+    in_document_prefix: bool,
     /// Whether all characters encountered since the last newline were whitespace.
     leading_whitespace: bool,
     /// Whether we started a flow mapping.
@@ -433,11 +1298,34 @@ pub struct Scanner<T> {
     /// [`Possible`]: ImplicitMappingState::Possible
     /// [`Inside`]: ImplicitMappingState::Inside
     implicit_flow_mapping_states: Vec<ImplicitMappingState>,
+    /// Buffer reused for the contents of scalars.
+    ///
+    /// Scalars which can be borrowed from the input are still accumulated here, which spares an
+    /// allocation per scalar. See [`Self::scalar_value`].
+    buf_scalar: String,
+    /// Buffer reused for the line break preceding a line fold in scalars.
+    buf_leading_break: String,
+    /// Buffer reused for the line breaks following a line fold in scalars.
+    buf_trailing_breaks: String,
+    /// Buffer reused for the whitespace between the words of scalars.
+    buf_whitespaces: String,
+    /// The comments that have been scanned but not yet taken with [`Self::pop_comment_before`].
+    comments: VecDeque<Comment<'input>>,
+    /// The warnings that have been found but not yet taken with [`Self::take_warnings`].
+    warnings: Vec<Warning>,
+    /// The options of the scanner, which ignores those that only apply to the parser.
+    options: ParserOptions,
+    /// The version of YAML by which the current document is scanned.
+    version: YamlVersion,
+    /// The version named by the `%YAML` directive of the next document, if any.
+    ///
+    /// It takes effect at the `---` marker which starts the document.
+    directive_version: Option<YamlVersion>,
 }
 
-impl<T: Iterator<Item = char>> Iterator for Scanner<T> {
-    type Item = Token;
-    fn next(&mut self) -> Option<Token> {
+impl<'input, T: Input<'input>> Iterator for Scanner<'input, T> {
+    type Item = Token<'input>;
+    fn next(&mut self) -> Option<Self::Item> {
         if self.error.is_some() {
             return None;
         }
@@ -452,6 +1340,11 @@ impl<T: Iterator<Item = char>> Iterator for Scanner<T> {
             }
             Ok(tok) => tok,
             Err(e) => {
+                let e = self
+                    .flow_starts
+                    .iter()
+                    .rev()
+                    .fold(e, |e, &(context, start)| e.with_context(context, start));
                 self.error = Some(e);
                 None
             }
@@ -462,15 +1355,26 @@ impl<T: Iterator<Item = char>> Iterator for Scanner<T> {
 /// A convenience alias for scanner functions that may fail without returning a value.
 pub type ScanResult = Result<(), ScanError>;
 
-impl<T: Iterator<Item = char>> Scanner<T> {
+impl<R: std::io::Read> Scanner<'_, ReaderInput<R>> {
+    /// Creates the YAML tokenizer, reading UTF-8 from the given reader.
+    ///
+    /// See [`ReaderInput`] for details about how the input is read.
+    pub fn new_from_reader(reader: R) -> Self {
+        Scanner::new(ReaderInput::new(reader))
+    }
+}
+
+impl<'input, T: Input<'input>> Scanner<'input, T> {
     /// Creates the YAML tokenizer.
-    pub fn new(rdr: T) -> Scanner<T> {
+    pub fn new(rdr: T) -> Scanner<'input, T> {
         Scanner {
             rdr,
             buffer: ArrayDeque::new(),
             mark: Marker::new(0, 1, 0),
             tokens: VecDeque::new(),
+            token_marker: Marker::new(0, 1, 0),
             error: None,
+            input_error: None,
 
             stream_start_produced: false,
             stream_end_produced: false,
@@ -480,42 +1384,313 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             indent: -1,
             indents: Vec::new(),
             flow_level: 0,
+            flow_starts: Vec::new(),
             tokens_parsed: 0,
             token_available: false,
+            in_document_prefix: true,
             leading_whitespace: true,
             flow_mapping_started: false,
             implicit_flow_mapping_states: vec![],
+            buf_scalar: String::new(),
+            buf_leading_break: String::new(),
+            buf_trailing_breaks: String::new(),
+            buf_whitespaces: String::new(),
+            comments: VecDeque::new(),
+            warnings: Vec::new(),
+            options: ParserOptions::default(),
+            version: YamlVersion::default(),
+            directive_version: None,
+        }
+    }
+
+    /// Set the options of the scanner.
+    ///
+    /// The options which only apply to the parser, [`ParserOptions::keep_tags`] and
+    /// [`ParserOptions::recover_from_errors`], are ignored.
+    #[must_use]
+    pub fn options(mut self, options: ParserOptions) -> Self {
+        self.options = options;
+        self.version = options.yaml_version;
+        self
+    }
+
+    /// Return an error if `len`, the number of bytes buffered so far for the scalar starting at
+    /// `start_mark`, exceeds [`Limits::max_scalar_bytes`].
+    ///
+    /// This is called as characters are buffered, so that no more than the limit is buffered for
+    /// a single token. Whitespace and line breaks held until the next content character count
+    /// toward `len`. Comments, anchors, tags and directives are bound by the same limit.
+    #[inline]
+    fn check_scalar_length(&self, len: usize, start_mark: Marker) -> ScanResult {
+        if len > self.options.limits.max_scalar_bytes {
+            Err(ScanError::from_kind(
+                start_mark,
+                ScanErrorKind::ScalarLimitExceeded,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Take the next recorded comment, if it starts before `mark`.
+    ///
+    /// Comments are returned in the order they appear in the source. This always returns `None`
+    /// unless [`ParserOptions::keep_comments`] is set.
+    pub fn pop_comment_before(&mut self, mark: Marker) -> Option<Comment<'input>> {
+        if self.comments.front()?.span.start.index < mark.index {
+            self.comments.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Take the warnings found since the last call, in the order they were found.
+    ///
+    /// Warnings report input accepted in [lenient](ParserOptions::lenient) mode.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Accept input which is only valid in [lenient](ParserOptions::lenient) mode.
+    ///
+    /// In lenient mode, record a warning of the given kind at `mark`. Otherwise, return `error`.
+    fn accept_leniently(
+        &mut self,
+        mark: Marker,
+        kind: WarningKind,
+        error: ScanError,
+    ) -> ScanResult {
+        if self.options.lenient {
+            self.warnings.push(Warning::new(mark, kind));
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Return the marker at which the parser reports the last token returned by
+    /// [`Self::next_token`].
+    ///
+    /// This is the start of the token, except for a few tokens the parser has always reported
+    /// elsewhere: block entries after the whitespace following their `-`, block scalars at the
+    /// start of their contents and the start of block mappings and of flow mappings without braces
+    /// after their first `:`.
+    #[inline]
+    pub(crate) fn token_marker(&self) -> Marker {
+        self.token_marker
+    }
+
+    /// Get a copy of the last error that was encountered, if any.
+    ///
+    /// This does not clear the error state and further calls to [`Self::get_error`] will return (a
+    /// clone of) the same error.
+    #[inline]
+    pub fn get_error(&self) -> Option<ScanError> {
+        self.error.clone()
+    }
+
+    /// Clear the error state and skip input so that scanning can resume after an error on `line`.
+    ///
+    /// Tokens of that line which have not been returned yet are dropped, except for those opening
+    /// or closing block collections, which must stay balanced with our indentation levels. If we
+    /// are within flow collections, their contents are skipped up to their closing bracket, or up
+    /// to a line indented at or below the block collection they are in, from which scanning then
+    /// resumes. If we are on `line` (or after skipping flow collections), the rest of the line is
+    /// skipped.
+    pub(crate) fn resync(&mut self, line: usize) {
+        if self.stream_end_produced {
+            return;
+        }
+        self.error = None;
+        self.tokens.retain(|(Token(span, tok), _)| {
+            span.start.line > line
+                || matches!(
+                    tok,
+                    TokenType::BlockMappingStart
+                        | TokenType::BlockSequenceStart
+                        | TokenType::BlockEnd
+                        | TokenType::StreamEnd
+                )
+        });
+        self.token_available = false;
+
+        let mut depth = usize::from(self.flow_level);
+        let mut in_flow = depth > 0;
+        // Whether the previous character separates tokens, so that a quote or `#` starts a quoted
+        // scalar or a comment, whose brackets must not be counted.
+        let mut after_separator = true;
+        self.lookahead(4);
+        while depth > 0 && !is_z(self.ch()) && !self.next_is_document_indicator() {
+            let c = self.ch();
+            match c {
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                '\'' | '"' if after_separator => {
+                    self.skip_quoted_for_resync(c);
+                    after_separator = false;
+                    self.lookahead(4);
+                    continue;
+                }
+                '#' if after_separator => {
+                    while !self.next_is_breakz() {
+                        self.skip_non_blank();
+                    }
+                    self.lookahead(4);
+                    continue;
+                }
+                _ => {}
+            }
+            after_separator = self.is_blank_or_breakz(c) || matches!(c, '[' | '{' | ',');
+            if self.is_break(c) {
+                self.skip_linebreak();
+                self.lookahead(1);
+                while self.ch() == ' ' {
+                    self.skip_blank();
+                    self.lookahead(1);
+                }
+                // Flow collections cannot go on at or below the indentation of the block
+                // collection they are in, except for their closing bracket.
+                if (self.mark.col as isize) <= self.indent
+                    && !self.is_breakz(self.ch())
+                    && !matches!(self.ch(), ']' | '}' | '#')
+                {
+                    in_flow = false;
+                    break;
+                }
+            } else {
+                self.skip_non_blank();
+            }
+            self.lookahead(4);
+        }
+        if in_flow || self.mark.line == line {
+            while !self.next_is_breakz() {
+                self.skip_non_blank();
+            }
+            self.lookahead(2);
+            self.skip_linebreak();
+        }
+
+        self.flow_level = 0;
+        self.flow_starts.clear();
+        self.flow_mapping_started = false;
+        self.implicit_flow_mapping_states.clear();
+        self.simple_keys.truncate(1);
+        for sk in &mut self.simple_keys {
+            sk.possible = false;
+        }
+        self.simple_key_allowed = self.leading_whitespace;
+        self.adjacent_value_allowed_at = 0;
+    }
+
+    /// Skip a quoted scalar starting with `quote` while resynchronizing after an error.
+    ///
+    /// The scalar ends at its closing quote, at the end of the stream or at a document indicator.
+    /// Escaped quotes in double-quoted scalars and doubled quotes in single-quoted scalars are
+    /// part of the scalar.
+    fn skip_quoted_for_resync(&mut self, quote: char) {
+        self.skip_non_blank();
+        self.lookahead(4);
+        while !is_z(self.ch()) && !self.next_is_document_indicator() {
+            let c = self.ch();
+            if self.is_break(c) {
+                self.skip_linebreak();
+            } else if (c == '\\' && quote == '"' && !self.is_breakz(self.buffer[1]))
+                || (c == '\'' && quote == '\'' && self.buffer[1] == '\'')
+            {
+                // An escaped character or a doubled single quote.
+                self.skip_non_blank();
+                self.skip_non_blank();
+            } else if c == quote {
+                self.skip_non_blank();
+                return;
+            } else {
+                self.skip_non_blank();
+            }
+            self.lookahead(4);
         }
     }
 
-    /// Get a copy of the last error that was encountered, if any.
-    ///
-    /// This does not clear the error state and further calls to [`Self::get_error`] will return (a
-    /// clone of) the same error.
-    #[inline]
-    pub fn get_error(&self) -> Option<ScanError> {
-        self.error.clone()
-    }
-
     /// Fill `self.buffer` with at least `count` characters.
     ///
     /// The characters that are extracted this way are not consumed but only placed in the buffer.
+    /// Asking for more characters than the buffer holds records an
+    /// [`ScanErrorKind::InconsistentState`] error in [`Self::error`], which stops the scanner once
+    /// the token being scanned is returned.
     #[inline]
     fn lookahead(&mut self, count: usize) {
         if self.buffer.len() >= count {
             return;
         }
         for _ in 0..(count - self.buffer.len()) {
-            self.buffer
-                .push_back(self.rdr.next().unwrap_or('\0'))
-                .unwrap();
+            let c = self.read_ch();
+            if self.buffer.push_back(c).is_err() {
+                if self.error.is_none() {
+                    self.error = Some(self.inconsistent_state());
+                }
+                return;
+            }
+        }
+    }
+
+    /// Read the next character from the input, or `\0` if the input is exhausted.
+    ///
+    /// If the input returns an error, it is stored in [`Self::input_error`] and `\0` is returned.
+    #[inline]
+    fn read_ch(&mut self) -> char {
+        match self.rdr.next_char() {
+            Ok(Some(c)) => c,
+            Ok(None) => '\0',
+            Err(e) => {
+                if self.input_error.is_none() {
+                    self.input_error = Some(e);
+                }
+                '\0'
+            }
+        }
+    }
+
+    /// Return the input error as a [`ScanError`] if the scanner has reached the point at which the
+    /// input failed.
+    ///
+    /// When the input fails, the scanner sees the input as ending there. Errors about an
+    /// unexpected end of stream would then hide the real cause, which this function retrieves.
+    fn take_input_error(&mut self) -> Option<ScanError> {
+        if self.input_error.is_some() && self.buffer.front().map_or(true, |c| is_z(*c)) {
+            self.input_error.take().map(|e| {
+                let info = e.to_string();
+                let (kind, io_error) = match e {
+                    InputError::Io(e) => (ScanErrorKind::Io(e.kind()), Some(Arc::new(e))),
+                    InputError::InvalidSequence(encoding) => {
+                        (ScanErrorKind::InvalidSequence(encoding), None)
+                    }
+                };
+                ScanError {
+                    mark: self.mark,
+                    kind,
+                    info,
+                    context: Vec::new(),
+                    details: io_error.map(|e| Box::new(ErrorDetails::Io(e))),
+                }
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Remove the next character from the buffer, keeping track of its size in bytes.
+    ///
+    /// [`Self::mark`] needs to be updated by the caller.
+    #[inline]
+    fn pop_ch(&mut self) {
+        if let Some(c) = self.buffer.pop_front() {
+            self.mark.byte_offset += c.len_utf8();
         }
     }
 
     /// Consume the next character. It is assumed the next character is a blank.
     #[inline]
     fn skip_blank(&mut self) {
-        self.buffer.pop_front();
+        self.pop_ch();
 
         self.mark.index += 1;
         self.mark.col += 1;
@@ -524,7 +1699,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     /// Consume the next character. It is assumed the next character is not a blank.
     #[inline]
     fn skip_non_blank(&mut self) {
-        self.buffer.pop_front();
+        self.pop_ch();
 
         self.mark.index += 1;
         self.mark.col += 1;
@@ -534,17 +1709,29 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     /// Consume the next characters. It is assumed none of the next characters are blanks.
     #[inline]
     fn skip_n_non_blank(&mut self, n: usize) {
-        self.buffer.drain(0..n);
+        for _ in 0..n {
+            self.pop_ch();
+        }
 
         self.mark.index += n;
         self.mark.col += n;
         self.leading_whitespace = false;
     }
 
+    /// Consume the next character. It is assumed the next character is a byte order mark.
+    ///
+    /// The byte order mark is not considered to take up a column.
+    #[inline]
+    fn skip_bom(&mut self) {
+        self.pop_ch();
+
+        self.mark.index += 1;
+    }
+
     /// Consume the next character. It is assumed the next character is a newline.
     #[inline]
     fn skip_nl(&mut self) {
-        self.buffer.pop_front();
+        self.pop_ch();
 
         self.mark.index += 1;
         self.mark.col = 0;
@@ -560,11 +1747,40 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             // will be reset by `skip_nl`.
             self.skip_blank();
             self.skip_nl();
-        } else if is_break(self.buffer[0]) {
+        } else if self.is_break(self.buffer[0]) {
             self.skip_nl();
         }
     }
 
+    /// Check whether the character is a line break in the current document.
+    ///
+    /// YAML 1.1 has more line breaks than YAML 1.2, see [`YamlVersion`].
+    #[inline]
+    fn is_break(&self, c: char) -> bool {
+        is_break(c) || (self.version == YamlVersion::V1_1 && is_yaml_1_1_break(c))
+    }
+
+    /// Check whether the character is nil or a line break in the current document.
+    #[inline]
+    fn is_breakz(&self, c: char) -> bool {
+        self.is_break(c) || is_z(c)
+    }
+
+    /// Check whether the character is nil, a whitespace or a line break in the current document.
+    #[inline]
+    fn is_blank_or_breakz(&self, c: char) -> bool {
+        is_blank(c) || self.is_breakz(c)
+    }
+
+    /// Look for the next character and return whether it is nil or a line break.
+    ///
+    /// The character is not consumed.
+    #[inline]
+    fn next_is_breakz(&mut self) -> bool {
+        self.lookahead(1);
+        self.is_breakz(self.ch())
+    }
+
     /// Return the next character in the buffer.
     ///
     /// The character is not consumed.
@@ -589,7 +1805,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     #[inline]
     #[must_use]
     fn raw_read_ch(&mut self) -> char {
-        self.rdr.next().unwrap_or('\0')
+        self.read_ch()
     }
 
     /// Return whether the next character is `c`.
@@ -616,9 +1832,11 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.mark
     }
 
-    // Read and consume a line break (either `\r`, `\n` or `\r\n`).
+    // Read and consume a line break (either `\r`, `\n`, `\r\n` or, in YAML 1.1, `\u{85}`,
+    // `\u{2028}` or `\u{2029}`).
     //
-    // A `\n` is pushed into `s`.
+    // A `\n` is pushed into `s`, except for YAML 1.1 line and paragraph separators which are
+    // pushed as is.
     //
     // # Panics (in debug)
     // If the next characters do not correspond to a line break.
@@ -626,43 +1844,66 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn read_break(&mut self, s: &mut String) {
         let c = self.buffer[0];
         let nc = self.buffer[1];
-        debug_assert!(is_break(c));
+        debug_assert!(self.is_break(c));
         if c == '\r' && nc == '\n' {
             self.skip_blank();
         }
         self.skip_nl();
 
-        s.push('\n');
+        if c == '\u{2028}' || c == '\u{2029}' {
+            s.push(c);
+        } else {
+            s.push('\n');
+        }
     }
 
     /// Check whether the next characters correspond to an end of document.
-    ///
-    /// [`Self::lookahead`] must have been called before calling this function.
-    fn next_is_document_end(&self) -> bool {
-        assert!(self.buffer.len() >= 4);
+    fn next_is_document_end(&mut self) -> bool {
+        self.lookahead(4);
         self.buffer[0] == '.'
             && self.buffer[1] == '.'
             && self.buffer[2] == '.'
-            && is_blank_or_breakz(self.buffer[3])
+            && self.is_blank_or_breakz(self.buffer[3])
     }
 
     /// Check whether the next characters correspond to a document indicator.
-    ///
-    /// [`Self::lookahead`] must have been called before calling this function.
     #[inline]
-    fn next_is_document_indicator(&self) -> bool {
-        assert!(self.buffer.len() >= 4);
+    fn next_is_document_indicator(&mut self) -> bool {
+        self.lookahead(4);
         self.mark.col == 0
             && (((self.buffer[0] == '-') && (self.buffer[1] == '-') && (self.buffer[2] == '-'))
                 || ((self.buffer[0] == '.') && (self.buffer[1] == '.') && (self.buffer[2] == '.')))
-            && is_blank_or_breakz(self.buffer[3])
+            && self.is_blank_or_breakz(self.buffer[3])
+    }
+
+    /// Queue a token, reported by the parser at its start.
+    fn push_token(&mut self, tok: Token<'input>) {
+        let marker = tok.0.start;
+        self.tokens.push_back((tok, marker));
+    }
+
+    /// Insert a token so that it is the `token_number`th token of the stream, reported by the
+    /// parser at `marker`.
+    ///
+    /// The position must not have been handed out yet.
+    fn insert_token(
+        &mut self,
+        token_number: usize,
+        tok: Token<'input>,
+        marker: Marker,
+    ) -> ScanResult {
+        match token_number.checked_sub(self.tokens_parsed) {
+            Some(pos) if pos <= self.tokens.len() => {
+                self.tokens.insert(pos, (tok, marker));
+                Ok(())
+            }
+            _ => Err(self.inconsistent_state()),
+        }
     }
 
-    /// Insert a token at the given position.
-    fn insert_token(&mut self, pos: usize, tok: Token) {
-        let old_len = self.tokens.len();
-        assert!(pos <= old_len);
-        self.tokens.insert(pos, tok);
+    /// Return the error for a state of the scanner that should be impossible.
+    fn inconsistent_state(&self) -> ScanError {
+        ScanError::from_kind(self.mark, ScanErrorKind::InconsistentState)
     }
 
     fn allow_simple_key(&mut self) {
@@ -685,6 +1926,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return Ok(());
         }
         self.skip_to_next_token()?;
+        self.in_document_prefix = false;
 
         debug_print!(
             "  \x1B[38;5;244m\u{2192} fetch_next_token after whitespace {:?} {:?}\x1B[m",
@@ -695,7 +1937,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.stale_simple_keys()?;
 
         let mark = self.mark;
-        self.unroll_indent(mark.col as isize);
+        self.unroll_indent(mark.col as isize)?;
 
         self.lookahead(4);
 
@@ -713,7 +1955,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             && self.buffer[0] == '-'
             && self.buffer[1] == '-'
             && self.buffer[2] == '-'
-            && is_blank_or_breakz(self.buffer[3])
+            && self.is_blank_or_breakz(self.buffer[3])
         {
             self.fetch_document_indicator(TokenType::DocumentStart)?;
             return Ok(());
@@ -723,21 +1965,24 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             && self.buffer[0] == '.'
             && self.buffer[1] == '.'
             && self.buffer[2] == '.'
-            && is_blank_or_breakz(self.buffer[3])
+            && self.is_blank_or_breakz(self.buffer[3])
         {
             self.fetch_document_indicator(TokenType::DocumentEnd)?;
             self.skip_ws_to_eol(SkipTabs::Yes)?;
-            if !is_breakz(self.ch()) {
-                return Err(ScanError::new_str(
+            if !self.is_breakz(self.ch()) {
+                return Err(ScanError::from_kind(
                     self.mark,
-                    "invalid content after document end marker",
+                    ScanErrorKind::ContentAfterDocumentEnd,
                 ));
             }
             return Ok(());
         }
 
         if (self.mark.col as isize) < self.indent {
-            return Err(ScanError::new_str(self.mark, "invalid indentation"));
+            return Err(ScanError::from_kind(
+                self.mark,
+                ScanErrorKind::InvalidIndentation,
+            ));
         }
 
         let c = self.buffer[0];
@@ -748,9 +1993,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             ']' => self.fetch_flow_collection_end(TokenType::FlowSequenceEnd),
             '}' => self.fetch_flow_collection_end(TokenType::FlowMappingEnd),
             ',' => self.fetch_flow_entry(),
-            '-' if is_blank_or_breakz(nc) => self.fetch_block_entry(),
-            '?' if is_blank_or_breakz(nc) => self.fetch_key(),
-            ':' if is_blank_or_breakz(nc) => self.fetch_value(),
+            '-' if self.is_blank_or_breakz(nc) => self.fetch_block_entry(),
+            '?' if self.is_blank_or_breakz(nc) => self.fetch_key(),
+            ':' if self.is_blank_or_breakz(nc) => self.fetch_value(),
             ':' if self.flow_level > 0
                 && (is_flow(nc) || self.mark.index == self.adjacent_value_allowed_at) =>
             {
@@ -768,13 +2013,25 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             '\'' => self.fetch_flow_scalar(true),
             '"' => self.fetch_flow_scalar(false),
             // plain scalar
-            '-' if !is_blank_or_breakz(nc) => self.fetch_plain_scalar(),
-            ':' | '?' if !is_blank_or_breakz(nc) && self.flow_level == 0 => {
+            '-' if !self.is_blank_or_breakz(nc) => self.fetch_plain_scalar(),
+            ':' | '?' if !self.is_blank_or_breakz(nc) && self.flow_level == 0 => {
+                self.fetch_plain_scalar()
+            }
+            '%' => Err(ScanError::from_kind(
+                self.mark,
+                ScanErrorKind::UnexpectedCharacter(c),
+            )),
+            '@' | '`' => {
+                self.accept_leniently(
+                    self.mark,
+                    WarningKind::ReservedIndicator(c),
+                    ScanError::from_kind(self.mark, ScanErrorKind::UnexpectedCharacter(c)),
+                )?;
                 self.fetch_plain_scalar()
             }
-            '%' | '@' | '`' => Err(ScanError::new(
+            '\u{FEFF}' => Err(ScanError::from_kind(
                 self.mark,
-                format!("unexpected character: `{c}'"),
+                ScanErrorKind::MisplacedByteOrderMark,
             )),
             _ => self.fetch_plain_scalar(),
         }
@@ -783,20 +2040,20 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     /// Return the next token in the stream.
     /// # Errors
     /// Returns `ScanError` when scanning fails to find an expected next token.
-    pub fn next_token(&mut self) -> Result<Option<Token>, ScanError> {
+    pub fn next_token(&mut self) -> Result<Option<Token<'input>>, ScanError> {
         if self.stream_end_produced {
             return Ok(None);
         }
 
         if !self.token_available {
-            self.fetch_more_tokens()?;
+            if let Err(e) = self.fetch_more_tokens() {
+                return Err(self.take_input_error().unwrap_or(e));
+            }
         }
-        let Some(t) = self.tokens.pop_front() else {
-            return Err(ScanError::new_str(
-                self.mark,
-                "did not find expected next token",
-            ));
+        let Some((t, marker)) = self.tokens.pop_front() else {
+            return Err(ScanError::from_kind(self.mark, ScanErrorKind::NoNextToken));
         };
+        self.token_marker = marker;
         self.token_available = false;
         self.tokens_parsed += 1;
 
@@ -852,7 +2109,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     && (sk.mark.line < self.mark.line || sk.mark.index + 1024 < self.mark.index)
             {
                 if sk.required {
-                    return Err(ScanError::new_str(self.mark, "simple key expect ':'"));
+                    return Err(ScanError::from_kind(
+                        self.mark,
+                        ScanErrorKind::SimpleKeyExpectedColon,
+                    ));
                 }
                 sk.possible = false;
             }
@@ -867,7 +2127,6 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     /// one.
     fn skip_to_next_token(&mut self) -> ScanResult {
         loop {
-            // TODO(chenyh) BOM
             match self.look_ch() {
                 // Tabs may not be used as indentation.
                 // "Indentation" only exists as long as a block is started, but does not exist
@@ -879,34 +2138,90 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     && self.leading_whitespace
                     && (self.mark.col as isize) < self.indent =>
                 {
+                    let tab_mark = self.mark;
                     self.skip_ws_to_eol(SkipTabs::Yes)?;
                     // If we have content on that line with a tab, return an error.
-                    if !is_breakz(self.ch()) {
-                        return Err(ScanError::new_str(
-                            self.mark,
-                            "tabs disallowed within this context (block indentation)",
-                        ));
+                    if !self.is_breakz(self.ch()) {
+                        self.accept_leniently(
+                            tab_mark,
+                            WarningKind::TabIndentation,
+                            ScanError::from_kind(self.mark, ScanErrorKind::TabsInBlockIndentation),
+                        )?;
                     }
                 }
                 '\t' | ' ' => self.skip_blank(),
-                '\n' | '\r' => {
+                c if self.is_break(c) => {
                     self.lookahead(2);
                     self.skip_linebreak();
                     if self.flow_level == 0 {
                         self.allow_simple_key();
                     }
                 }
-                '#' => {
-                    while !is_breakz(self.look_ch()) {
-                        self.skip_non_blank();
-                    }
-                }
+                '#' => self.skip_comment()?,
+                '\u{FEFF}' if self.is_bom_allowed() => self.skip_bom(),
                 _ => break,
             }
         }
         Ok(())
     }
 
+    /// Skip over a comment, up to the end of the line.
+    ///
+    /// The comment is recorded into [`Self::comments`] if [`ParserOptions::keep_comments`] is set. This
+    /// function must be called with a `#` as the next character.
+    fn skip_comment(&mut self) -> ScanResult {
+        if !self.options.keep_comments {
+            while !self.next_is_breakz() {
+                self.skip_non_blank();
+            }
+            return Ok(());
+        }
+
+        let style = if self.leading_whitespace {
+            CommentStyle::FullLine
+        } else {
+            CommentStyle::Trailing
+        };
+        let start_mark = self.mark;
+        self.skip_non_blank();
+        let mut text = self.take_scalar_buffer();
+        while !self.next_is_breakz() {
+            text.push(self.ch());
+            self.check_scalar_length(text.len(), start_mark)?;
+            self.skip_non_blank();
+        }
+        let text = self.scalar_value(start_mark.byte_offset + 1, text);
+        self.comments.push_back(Comment {
+            span: Span::new(start_mark, self.mark),
+            text,
+            style,
+        });
+        Ok(())
+    }
+
+    /// Return whether a byte order mark may appear at the current position.
+    ///
+    /// As per the specification, a byte order mark may appear at the start of the stream and at
+    /// the start of any document. This function must be called with a BOM as the next character.
+    fn is_bom_allowed(&mut self) -> bool {
+        if self.mark.col != 0 {
+            return false;
+        }
+        // An explicit document may start right after another document, without a `...` marker.
+        self.in_document_prefix || self.next_is_bom_and_document_start()
+    }
+
+    /// Check whether the next characters are a byte order mark followed by a document start.
+    fn next_is_bom_and_document_start(&mut self) -> bool {
+        self.lookahead(5);
+        self.mark.col == 0
+            && self.buffer[0] == '\u{FEFF}'
+            && self.buffer[1] == '-'
+            && self.buffer[2] == '-'
+            && self.buffer[3] == '-'
+            && self.is_blank_or_breakz(self.buffer[4])
+    }
+
     /// Skip over YAML whitespace (` `, `\n`, `\r`).
     ///
     /// # Errors
@@ -920,7 +2235,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
                     need_whitespace = false;
                 }
-                '\n' | '\r' => {
+                c if self.is_break(c) => {
                     self.lookahead(2);
                     self.skip_linebreak();
                     if self.flow_level == 0 {
@@ -928,17 +2243,16 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     }
                     need_whitespace = false;
                 }
-                '#' => {
-                    while !is_breakz(self.look_ch()) {
-                        self.skip_non_blank();
-                    }
-                }
+                '#' => self.skip_comment()?,
                 _ => break,
             }
         }
 
         if need_whitespace {
-            Err(ScanError::new_str(self.mark(), "expected whitespace"))
+            Err(ScanError::from_kind(
+                self.mark(),
+                ScanErrorKind::ExpectedWhitespace,
+            ))
         } else {
             Ok(())
         }
@@ -960,16 +2274,14 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 }
                 // YAML comments must be preceded by whitespace.
                 '#' if !encountered_tab && !has_yaml_ws => {
-                    return Err(ScanError::new_str(
+                    self.accept_leniently(
                         self.mark,
-                        "comments must be separated from other tokens by whitespace",
-                    ));
-                }
-                '#' => {
-                    while !is_breakz(self.look_ch()) {
-                        self.skip_non_blank();
-                    }
+                        WarningKind::CommentWithoutWhitespace,
+                        ScanError::from_kind(self.mark, ScanErrorKind::CommentWithoutWhitespace),
+                    )?;
+                    self.skip_comment()?;
                 }
+                '#' => self.skip_comment()?,
                 _ => break,
             }
         }
@@ -982,12 +2294,19 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.indent = -1;
         self.stream_start_produced = true;
         self.allow_simple_key();
-        self.tokens
-            .push_back(Token(mark, TokenType::StreamStart(TEncoding::Utf8)));
+        self.push_token(Token(
+            Span::empty(mark),
+            TokenType::StreamStart(self.rdr.encoding()),
+        ));
         self.simple_keys.push(SimpleKey::new(Marker::new(0, 0, 0)));
     }
 
     fn fetch_stream_end(&mut self) -> ScanResult {
+        // If the input ended because of an error, report it rather than the end of the stream.
+        if let Some(e) = self.take_input_error() {
+            return Err(e);
+        }
+
         // force new line
         if self.mark.col != 0 {
             self.mark.col = 0;
@@ -998,33 +2317,35 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         // had. If one was required, however, that was an error and we must propagate it.
         for sk in &mut self.simple_keys {
             if sk.required && sk.possible {
-                return Err(ScanError::new_str(self.mark, "simple key expected"));
+                return Err(ScanError::from_kind(
+                    self.mark,
+                    ScanErrorKind::SimpleKeyExpected,
+                ));
             }
             sk.possible = false;
         }
 
-        self.unroll_indent(-1);
+        self.unroll_indent(-1)?;
         self.remove_simple_key()?;
         self.disallow_simple_key();
 
-        self.tokens
-            .push_back(Token(self.mark, TokenType::StreamEnd));
+        self.push_token(Token(Span::empty(self.mark), TokenType::StreamEnd));
         Ok(())
     }
 
     fn fetch_directive(&mut self) -> ScanResult {
-        self.unroll_indent(-1);
+        self.unroll_indent(-1)?;
         self.remove_simple_key()?;
 
         self.disallow_simple_key();
 
         let tok = self.scan_directive()?;
-        self.tokens.push_back(tok);
+        self.push_token(tok);
 
         Ok(())
     }
 
-    fn scan_directive(&mut self) -> Result<Token, ScanError> {
+    fn scan_directive(&mut self) -> Result<Token<'input>, ScanError> {
         let start_mark = self.mark;
         self.skip_non_blank();
 
@@ -1032,37 +2353,33 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let tok = match name.as_ref() {
             "YAML" => self.scan_version_directive_value(&start_mark)?,
             "TAG" => self.scan_tag_directive_value(&start_mark)?,
-            // XXX This should be a warning instead of an error
             _ => {
                 // skip current line
-                while !is_breakz(self.look_ch()) {
+                while !self.next_is_breakz() {
                     self.skip_non_blank();
                 }
-                // XXX return an empty TagDirective token
                 Token(
-                    start_mark,
-                    TokenType::TagDirective(String::new(), String::new()),
+                    Span::new(start_mark, self.mark),
+                    TokenType::ReservedDirective(name),
                 )
-                // return Err(ScanError::new_str(start_mark,
-                //     "while scanning a directive, found unknown directive name"))
             }
         };
 
         self.skip_ws_to_eol(SkipTabs::Yes)?;
 
-        if is_breakz(self.ch()) {
+        if self.is_breakz(self.ch()) {
             self.lookahead(2);
             self.skip_linebreak();
             Ok(tok)
         } else {
-            Err(ScanError::new_str(
+            Err(ScanError::from_kind(
                 start_mark,
-                "while scanning a directive, did not find expected comment or line break",
+                ScanErrorKind::DirectiveTrailingContent,
             ))
         }
     }
 
-    fn scan_version_directive_value(&mut self, mark: &Marker) -> Result<Token, ScanError> {
+    fn scan_version_directive_value(&mut self, mark: &Marker) -> Result<Token<'input>, ScanError> {
         while is_blank(self.look_ch()) {
             self.skip_blank();
         }
@@ -1070,16 +2387,20 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let major = self.scan_version_directive_number(mark)?;
 
         if self.ch() != '.' {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 *mark,
-                "while scanning a YAML directive, did not find expected digit or '.' character",
+                ScanErrorKind::VersionExpectedDigitOrDot,
             ));
         }
         self.skip_non_blank();
 
         let minor = self.scan_version_directive_number(mark)?;
+        self.directive_version = Some(YamlVersion::from_directive(major, minor));
 
-        Ok(Token(*mark, TokenType::VersionDirective(major, minor)))
+        Ok(Token(
+            Span::new(*mark, self.mark),
+            TokenType::VersionDirective(major, minor),
+        ))
     }
 
     fn scan_directive_name(&mut self) -> Result<String, ScanError> {
@@ -1087,20 +2408,21 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let mut string = String::new();
         while is_alpha(self.look_ch()) {
             string.push(self.ch());
+            self.check_scalar_length(string.len(), start_mark)?;
             self.skip_non_blank();
         }
 
         if string.is_empty() {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 start_mark,
-                "while scanning a directive, could not find expected directive name",
+                ScanErrorKind::DirectiveNameMissing,
             ));
         }
 
-        if !is_blank_or_breakz(self.ch()) {
-            return Err(ScanError::new_str(
+        if !self.is_blank_or_breakz(self.ch()) {
+            return Err(ScanError::from_kind(
                 start_mark,
-                "while scanning a directive, found unexpected non-alphabetical character",
+                ScanErrorKind::DirectiveNameInvalidCharacter(self.ch()),
             ));
         }
 
@@ -1112,9 +2434,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let mut length = 0usize;
         while let Some(digit) = self.look_ch().to_digit(10) {
             if length + 1 > 9 {
-                return Err(ScanError::new_str(
+                return Err(ScanError::from_kind(
                     *mark,
-                    "while scanning a YAML directive, found extremely long version number",
+                    ScanErrorKind::VersionNumberTooLong,
                 ));
             }
             length += 1;
@@ -1123,16 +2445,16 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if length == 0 {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 *mark,
-                "while scanning a YAML directive, did not find expected version number",
+                ScanErrorKind::VersionNumberMissing,
             ));
         }
 
         Ok(val)
     }
 
-    fn scan_tag_directive_value(&mut self, mark: &Marker) -> Result<Token, ScanError> {
+    fn scan_tag_directive_value(&mut self, mark: &Marker) -> Result<Token<'input>, ScanError> {
         /* Eat whitespaces. */
         while is_blank(self.look_ch()) {
             self.skip_blank();
@@ -1148,26 +2470,29 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         self.lookahead(1);
 
-        if is_blank_or_breakz(self.ch()) {
-            Ok(Token(*mark, TokenType::TagDirective(handle, prefix)))
+        if self.is_blank_or_breakz(self.ch()) {
+            Ok(Token(
+                Span::new(*mark, self.mark),
+                TokenType::TagDirective(handle, prefix),
+            ))
         } else {
-            Err(ScanError::new_str(
+            Err(ScanError::from_kind(
                 *mark,
-                "while scanning TAG, did not find expected whitespace or line break",
+                ScanErrorKind::TagDirectiveTrailingContent,
             ))
         }
     }
 
     fn fetch_tag(&mut self) -> ScanResult {
-        self.save_simple_key();
+        self.save_simple_key()?;
         self.disallow_simple_key();
 
         let tok = self.scan_tag()?;
-        self.tokens.push_back(tok);
+        self.push_token(tok);
         Ok(())
     }
 
-    fn scan_tag(&mut self) -> Result<Token, ScanError> {
+    fn scan_tag(&mut self) -> Result<Token<'input>, ScanError> {
         let start_mark = self.mark;
         let mut handle = String::new();
         let mut suffix;
@@ -1198,13 +2523,17 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
         }
 
-        if is_blank_or_breakz(self.look_ch()) || (self.flow_level > 0 && is_flow(self.ch())) {
+        self.lookahead(1);
+        if self.is_blank_or_breakz(self.ch()) || (self.flow_level > 0 && is_flow(self.ch())) {
             // XXX: ex 7.2, an empty scalar can follow a secondary tag
-            Ok(Token(start_mark, TokenType::Tag(handle, suffix)))
+            Ok(Token(
+                Span::new(start_mark, self.mark),
+                TokenType::Tag(handle, suffix),
+            ))
         } else {
-            Err(ScanError::new_str(
+            Err(ScanError::from_kind(
                 start_mark,
-                "while scanning a tag, did not find expected whitespace or line break",
+                ScanErrorKind::TagTrailingContent,
             ))
         }
     }
@@ -1212,9 +2541,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn scan_tag_handle(&mut self, directive: bool, mark: &Marker) -> Result<String, ScanError> {
         let mut string = String::new();
         if self.look_ch() != '!' {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 *mark,
-                "while scanning a tag, did not find expected '!'",
+                ScanErrorKind::TagHandleExpectedBang,
             ));
         }
 
@@ -1223,6 +2552,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         while is_alpha(self.look_ch()) {
             string.push(self.ch());
+            self.check_scalar_length(string.len(), *mark)?;
             self.skip_non_blank();
         }
 
@@ -1234,9 +2564,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             // It's either the '!' tag or not really a tag handle.  If it's a %TAG
             // directive, it's an error.  If it's a tag token, it must be a part of
             // URI.
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 *mark,
-                "while parsing a tag directive, did not find expected '!'",
+                ScanErrorKind::TagDirectiveExpectedBang,
             ));
         }
         Ok(string)
@@ -1256,9 +2586,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.skip_non_blank();
         } else if !is_tag_char(self.ch()) {
             // Otherwise, check if the first global tag character is valid.
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 *start_mark,
-                "invalid global tag character",
+                ScanErrorKind::InvalidGlobalTagCharacter(self.ch()),
             ));
         } else if self.ch() == '%' {
             // If it is valid and an escape sequence, escape it.
@@ -1276,6 +2606,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 string.push(self.ch());
                 self.skip_non_blank();
             }
+            self.check_scalar_length(string.len(), *start_mark)?;
         }
 
         Ok(string)
@@ -1297,12 +2628,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 string.push(self.ch());
                 self.skip_non_blank();
             }
+            self.check_scalar_length(string.len(), *start_mark)?;
         }
 
         if self.ch() != '>' {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 *start_mark,
-                "while scanning a verbatim tag, did not find the expected '>'",
+                ScanErrorKind::UnterminatedVerbatimTag,
             ));
         }
         self.skip_non_blank();
@@ -1334,15 +2666,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 string.push(self.ch());
                 self.skip_non_blank();
             }
+            self.check_scalar_length(string.len(), *mark)?;
 
             length += 1;
         }
 
         if length == 0 {
-            return Err(ScanError::new_str(
-                *mark,
-                "while parsing a tag, did not find expected tag URI",
-            ));
+            return Err(ScanError::from_kind(*mark, ScanErrorKind::TagUriMissing));
         }
 
         Ok(string)
@@ -1355,10 +2685,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.lookahead(3);
 
             if !(self.ch() == '%' && is_hex(self.buffer[1]) && is_hex(self.buffer[2])) {
-                return Err(ScanError::new_str(
-                    *mark,
-                    "while parsing a tag, did not find URI escaped octet",
-                ));
+                return Err(ScanError::from_kind(*mark, ScanErrorKind::InvalidUriEscape));
             }
 
             let octet = (as_hex(self.buffer[1]) << 4) + as_hex(self.buffer[2]);
@@ -1369,18 +2696,18 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     _ if octet & 0xF0 == 0xE0 => 3,
                     _ if octet & 0xF8 == 0xF0 => 4,
                     _ => {
-                        return Err(ScanError::new_str(
+                        return Err(ScanError::from_kind(
                             *mark,
-                            "while parsing a tag, found an incorrect leading UTF-8 octet",
+                            ScanErrorKind::InvalidUriLeadingOctet(octet),
                         ));
                     }
                 };
                 code = octet;
             } else {
                 if octet & 0xc0 != 0x80 {
-                    return Err(ScanError::new_str(
+                    return Err(ScanError::from_kind(
                         *mark,
-                        "while parsing a tag, found an incorrect trailing UTF-8 octet",
+                        ScanErrorKind::InvalidUriTrailingOctet(octet),
                     ));
                 }
                 code = (code << 8) + octet;
@@ -1396,48 +2723,55 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         match char::from_u32(code) {
             Some(ch) => Ok(ch),
-            None => Err(ScanError::new_str(
+            None => Err(ScanError::from_kind(
                 *mark,
-                "while parsing a tag, found an invalid UTF-8 codepoint",
+                ScanErrorKind::InvalidUriCodepoint(code),
             )),
         }
     }
 
     fn fetch_anchor(&mut self, alias: bool) -> ScanResult {
-        self.save_simple_key();
+        self.save_simple_key()?;
         self.disallow_simple_key();
 
         let tok = self.scan_anchor(alias)?;
 
-        self.tokens.push_back(tok);
+        self.push_token(tok);
 
         Ok(())
     }
 
-    fn scan_anchor(&mut self, alias: bool) -> Result<Token, ScanError> {
-        let mut string = String::new();
+    fn scan_anchor(&mut self, alias: bool) -> Result<Token<'input>, ScanError> {
+        let mut string = self.take_scalar_buffer();
         let start_mark = self.mark;
 
         self.skip_non_blank();
+        let start_offset = self.mark.byte_offset;
         while is_anchor_char(self.look_ch()) {
             string.push(self.ch());
+            self.check_scalar_length(string.len(), start_mark)?;
             self.skip_non_blank();
         }
 
         if string.is_empty() {
-            return Err(ScanError::new_str(start_mark, "while scanning an anchor or alias, did not find expected alphabetic or numeric character"));
+            return Err(ScanError::from_kind(
+                start_mark,
+                ScanErrorKind::AnchorNameMissing,
+            ));
         }
 
+        let name = self.scalar_value(start_offset, string);
+        let span = Span::new(start_mark, self.mark);
         if alias {
-            Ok(Token(start_mark, TokenType::Alias(string)))
+            Ok(Token(span, TokenType::Alias(name)))
         } else {
-            Ok(Token(start_mark, TokenType::Anchor(string)))
+            Ok(Token(span, TokenType::Anchor(name)))
         }
     }
 
-    fn fetch_flow_collection_start(&mut self, tok: TokenType) -> ScanResult {
+    fn fetch_flow_collection_start(&mut self, tok: TokenType<'input>) -> ScanResult {
         // The indicators '[' and '{' may start a simple key.
-        self.save_simple_key();
+        self.save_simple_key()?;
 
         self.roll_one_col_indent();
         self.increase_flow_level()?;
@@ -1446,27 +2780,38 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         let start_mark = self.mark;
         self.skip_non_blank();
+        let span = Span::new(start_mark, self.mark);
 
         if tok == TokenType::FlowMappingStart {
+            self.flow_starts
+                .push((ErrorContext::FlowMapping, start_mark));
             self.flow_mapping_started = true;
         } else {
+            self.flow_starts
+                .push((ErrorContext::FlowSequence, start_mark));
             self.implicit_flow_mapping_states
                 .push(ImplicitMappingState::Possible);
         }
 
         self.skip_ws_to_eol(SkipTabs::Yes)?;
 
-        self.tokens.push_back(Token(start_mark, tok));
+        self.push_token(Token(span, tok));
         Ok(())
     }
 
-    fn fetch_flow_collection_end(&mut self, tok: TokenType) -> ScanResult {
+    fn fetch_flow_collection_end(&mut self, tok: TokenType<'input>) -> ScanResult {
         self.remove_simple_key()?;
+        // Invalid input may close a collection with the wrong bracket, which the parser reports.
+        // Until then, keep the state of the collection that was actually opened.
+        let closes_sequence = matches!(
+            self.flow_starts.last(),
+            Some((ErrorContext::FlowSequence, _))
+        );
         self.decrease_flow_level();
 
         self.disallow_simple_key();
 
-        if matches!(tok, TokenType::FlowSequenceEnd) {
+        if closes_sequence {
             self.end_implicit_mapping(self.mark);
             // We are out exiting the flow sequence, nesting goes down 1 level.
             self.implicit_flow_mapping_states.pop();
@@ -1474,6 +2819,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         let start_mark = self.mark;
         self.skip_non_blank();
+        let span = Span::new(start_mark, self.mark);
         self.skip_ws_to_eol(SkipTabs::Yes)?;
 
         // A flow collection within a flow mapping can be a key. In that case, the value may be
@@ -1485,7 +2831,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.adjacent_value_allowed_at = self.mark.index;
         }
 
-        self.tokens.push_back(Token(start_mark, tok));
+        self.push_token(Token(span, tok));
         Ok(())
     }
 
@@ -1498,26 +2844,26 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         let start_mark = self.mark;
         self.skip_non_blank();
+        let span = Span::new(start_mark, self.mark);
         self.skip_ws_to_eol(SkipTabs::Yes)?;
 
-        self.tokens
-            .push_back(Token(start_mark, TokenType::FlowEntry));
+        self.push_token(Token(span, TokenType::FlowEntry));
         Ok(())
     }
 
     fn increase_flow_level(&mut self) -> ScanResult {
         self.simple_keys.push(SimpleKey::new(Marker::new(0, 0, 0)));
-        self.flow_level = self
-            .flow_level
-            .checked_add(1)
-            .ok_or_else(|| ScanError::new_str(self.mark, "recursion limit exceeded"))?;
+        self.flow_level = self.flow_level.checked_add(1).ok_or_else(|| {
+            ScanError::from_kind(self.mark, ScanErrorKind::RecursionLimitExceeded)
+        })?;
         Ok(())
     }
 
     fn decrease_flow_level(&mut self) {
         if self.flow_level > 0 {
             self.flow_level -= 1;
-            self.simple_keys.pop().unwrap();
+            self.flow_starts.pop();
+            self.simple_keys.pop();
         }
     }
 
@@ -1529,90 +2875,101 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn fetch_block_entry(&mut self) -> ScanResult {
         if self.flow_level > 0 {
             // - * only allowed in block
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 self.mark,
-                r#""-" is only valid inside a block"#,
+                ScanErrorKind::BlockEntryInFlow,
             ));
         }
         // Check if we are allowed to start a new entry.
         if !self.simple_key_allowed {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 self.mark,
-                "block sequence entries are not allowed in this context",
+                ScanErrorKind::BlockEntryNotAllowed,
             ));
         }
 
         // ???, fixes test G9HC.
-        if let Some(Token(mark, TokenType::Anchor(..) | TokenType::Tag(..))) = self.tokens.back() {
-            if self.mark.col == 0 && mark.col == 0 && self.indent > -1 {
-                return Err(ScanError::new_str(*mark, "invalid indentation for anchor"));
+        if let Some((Token(span, TokenType::Anchor(..) | TokenType::Tag(..)), _)) = self.tokens.back() {
+            if self.mark.col == 0 && span.start.col == 0 && self.indent > -1 {
+                return Err(ScanError::from_kind(
+                    span.start,
+                    ScanErrorKind::InvalidAnchorIndentation,
+                ));
             }
         }
 
         // Skip over the `-`.
         let mark = self.mark;
         self.skip_non_blank();
+        let span = Span::new(mark, self.mark);
 
         // generate BLOCK-SEQUENCE-START if indented
-        self.roll_indent(mark.col, None, TokenType::BlockSequenceStart, mark);
+        self.roll_indent(mark.col, None, TokenType::BlockSequenceStart, mark, mark)?;
         let found_tabs = self.skip_ws_to_eol(SkipTabs::Yes)?.found_tabs();
         self.lookahead(2);
-        if found_tabs && self.buffer[0] == '-' && is_blank_or_breakz(self.buffer[1]) {
-            return Err(ScanError::new_str(
+        if found_tabs && self.buffer[0] == '-' && self.is_blank_or_breakz(self.buffer[1]) {
+            return Err(ScanError::from_kind(
                 self.mark,
-                "'-' must be followed by a valid YAML whitespace",
+                ScanErrorKind::BlockEntryExpectedWhitespace,
             ));
         }
 
         self.skip_ws_to_eol(SkipTabs::No)?;
-        if is_break(self.look_ch()) || is_flow(self.ch()) {
+        self.lookahead(1);
+        if self.is_break(self.ch()) || is_flow(self.ch()) {
             self.roll_one_col_indent();
         }
 
         self.remove_simple_key()?;
         self.allow_simple_key();
 
+        // The parser reports the entry after the whitespace following the `-`.
         self.tokens
-            .push_back(Token(self.mark, TokenType::BlockEntry));
+            .push_back((Token(span, TokenType::BlockEntry), self.mark));
 
         Ok(())
     }
 
-    fn fetch_document_indicator(&mut self, t: TokenType) -> ScanResult {
-        self.unroll_indent(-1);
+    fn fetch_document_indicator(&mut self, t: TokenType<'input>) -> ScanResult {
+        self.unroll_indent(-1)?;
         self.remove_simple_key()?;
         self.disallow_simple_key();
+        self.in_document_prefix = t == TokenType::DocumentEnd;
+        // A `%YAML` directive only applies to the document it precedes.
+        self.version = match self.directive_version.take() {
+            Some(version) if t == TokenType::DocumentStart => version,
+            _ => self.options.yaml_version,
+        };
 
         let mark = self.mark;
 
         self.skip_n_non_blank(3);
 
-        self.tokens.push_back(Token(mark, t));
+        self.push_token(Token(Span::new(mark, self.mark), t));
         Ok(())
     }
 
     fn fetch_block_scalar(&mut self, literal: bool) -> ScanResult {
-        self.save_simple_key();
+        self.save_simple_key()?;
         self.allow_simple_key();
-        let tok = self.scan_block_scalar(literal)?;
+        let (tok, marker) = self.scan_block_scalar(literal)?;
 
-        self.tokens.push_back(tok);
+        self.tokens.push_back((tok, marker));
         Ok(())
     }
 
+    /// Scan a block scalar, starting at its `|` or `>` indicator.
+    ///
+    /// Return the token along with the marker at which the parser reports it: the start of the
+    /// first content line, or the indicator if the scalar ends with the stream.
     #[allow(clippy::too_many_lines)]
-    fn scan_block_scalar(&mut self, literal: bool) -> Result<Token, ScanError> {
+    fn scan_block_scalar(&mut self, literal: bool) -> Result<(Token<'input>, Marker), ScanError> {
         let start_mark = self.mark;
         let mut chomping = Chomping::Clip;
         let mut increment: usize = 0;
         let mut indent: usize = 0;
         let mut trailing_blank: bool;
         let mut leading_blank: bool = false;
-        let style = if literal {
-            TScalarStyle::Literal
-        } else {
-            TScalarStyle::Folded
-        };
 
         let mut string = String::new();
         let mut leading_break = String::new();
@@ -1632,9 +2989,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.skip_non_blank();
             if is_digit(self.look_ch()) {
                 if self.ch() == '0' {
-                    return Err(ScanError::new_str(
+                    return Err(ScanError::from_kind(
                         start_mark,
-                        "while scanning a block scalar, found an indentation indicator equal to 0",
+                        ScanErrorKind::BlockScalarZeroIndentation,
                     ));
                 }
                 increment = (self.ch() as usize) - ('0' as usize);
@@ -1642,9 +2999,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
         } else if is_digit(self.ch()) {
             if self.ch() == '0' {
-                return Err(ScanError::new_str(
+                return Err(ScanError::from_kind(
                     start_mark,
-                    "while scanning a block scalar, found an indentation indicator equal to 0",
+                    ScanErrorKind::BlockScalarZeroIndentation,
                 ));
             }
 
@@ -1660,26 +3017,29 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 self.skip_non_blank();
             }
         }
+        // The end of the scalar, pushed back to the end of each line of content.
+        let mut end_mark = self.mark;
 
         self.skip_ws_to_eol(SkipTabs::Yes)?;
 
         // Check if we are at the end of the line.
-        if !is_breakz(self.look_ch()) {
-            return Err(ScanError::new_str(
+        self.lookahead(1);
+        if !self.is_breakz(self.ch()) {
+            return Err(ScanError::from_kind(
                 start_mark,
-                "while scanning a block scalar, did not find expected comment or line break",
+                ScanErrorKind::BlockScalarTrailingContent,
             ));
         }
 
-        if is_break(self.ch()) {
+        if self.is_break(self.ch()) {
             self.lookahead(2);
             self.read_break(&mut chomping_break);
         }
 
         if self.look_ch() == '\t' {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 start_mark,
-                "a block scalar content cannot start with a tab",
+                ScanErrorKind::BlockScalarStartsWithTab,
             ));
         }
 
@@ -1693,11 +3053,26 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         // Scan the leading line breaks and determine the indentation level if needed.
         if indent == 0 {
-            self.skip_block_scalar_first_line_indent(&mut indent, &mut trailing_breaks);
+            self.skip_block_scalar_first_line_indent(
+                &mut indent,
+                &mut trailing_breaks,
+                start_mark,
+            )?;
         } else {
-            self.skip_block_scalar_indent(indent, &mut trailing_breaks);
+            self.skip_block_scalar_indent(indent, &mut trailing_breaks, 0, start_mark)?;
         }
 
+        let header = BlockScalarHeader {
+            chomping,
+            indentation_indicator: (increment > 0).then_some(increment),
+            indent,
+        };
+        let style = if literal {
+            TScalarStyle::Literal(header)
+        } else {
+            TScalarStyle::Folded(header)
+        };
+
         // We have an end-of-stream with no content, e.g.:
         // ```yaml
         // - |+
@@ -1717,18 +3092,25 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 // Otherwise, the newline after chomping is ignored.
                 Chomping::Keep => trailing_breaks,
             };
-            return Ok(Token(start_mark, TokenType::Scalar(style, contents)));
+            return Ok((
+                Token(
+                    Span::new(start_mark, end_mark),
+                    TokenType::Scalar(style, contents.into()),
+                ),
+                start_mark,
+            ));
         }
 
         if self.mark.col < indent && (self.mark.col as isize) > self.indent {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 self.mark,
-                "wrongly indented line in block scalar",
+                ScanErrorKind::BlockScalarIndentation,
             ));
         }
 
+        let marker = self.mark;
+
         let mut line_buffer = String::with_capacity(100);
-        let start_mark = self.mark;
         while self.mark.col == indent && !is_z(self.ch()) {
             if indent == 0 {
                 self.lookahead(4);
@@ -1739,7 +3121,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
             // We are at the first content character of a content line.
             trailing_blank = is_blank(self.ch());
-            if !literal && !leading_break.is_empty() && !leading_blank && !trailing_blank {
+            // YAML 1.1 line and paragraph separators are not folded.
+            if !literal && leading_break == "\n" && !leading_blank && !trailing_blank {
                 string.push_str(&trailing_breaks);
                 if trailing_breaks.is_empty() {
                     string.push(' ');
@@ -1754,7 +3137,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
             leading_blank = is_blank(self.ch());
 
-            self.scan_block_scalar_content_line(&mut string, &mut line_buffer);
+            self.scan_block_scalar_content_line(&mut string, &mut line_buffer, start_mark)?;
+            end_mark = self.mark;
 
             // break on EOF
             if is_z(self.ch()) {
@@ -1765,7 +3149,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.read_break(&mut leading_break);
 
             // Eat the following indentation spaces and line breaks.
-            self.skip_block_scalar_indent(indent, &mut trailing_breaks);
+            let buffered = string.len() + leading_break.len();
+            self.skip_block_scalar_indent(indent, &mut trailing_breaks, buffered, start_mark)?;
         }
 
         // Chomp the tail.
@@ -1782,8 +3167,15 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         if chomping == Chomping::Keep {
             string.push_str(&trailing_breaks);
         }
-
-        Ok(Token(start_mark, TokenType::Scalar(style, string)))
+        self.check_scalar_length(string.len(), start_mark)?;
+
+        Ok((
+            Token(
+                Span::new(start_mark, end_mark),
+                TokenType::Scalar(style, string.into()),
+            ),
+            marker,
+        ))
     }
 
     /// Retrieve the contents of the line, parsing it as a block scalar.
@@ -1795,10 +3187,20 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     ///
     /// This function assumed the first character to read is the first content character in the
     /// line. This function does not consume the line break character(s) after the line.
-    fn scan_block_scalar_content_line(&mut self, string: &mut String, line_buffer: &mut String) {
+    ///
+    /// # Errors
+    /// Return an error if the scalar starting at `start_mark` exceeds
+    /// [`Limits::max_scalar_bytes`].
+    fn scan_block_scalar_content_line(
+        &mut self,
+        string: &mut String,
+        line_buffer: &mut String,
+        start_mark: Marker,
+    ) -> ScanResult {
         // Start by evaluating characters in the buffer.
-        while !self.buffer.is_empty() && !is_breakz(self.ch()) {
+        while !self.buffer.is_empty() && !self.is_breakz(self.ch()) {
             string.push(self.ch());
+            self.check_scalar_length(string.len(), start_mark)?;
             // We may technically skip non-blank characters. However, the only distinction is
             // to determine what is leading whitespace and what is not. Here, we read the
             // contents of the line until either eof or a linebreak. We know we will not read
@@ -1816,30 +3218,46 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             // UTF-8). We can then use the internal `line_buffer` `Vec` to push data into `string`
             // (using `String::push_str`).
             let mut c = self.raw_read_ch();
-            while !is_breakz(c) {
+            while !self.is_breakz(c) {
                 line_buffer.push(c);
+                self.check_scalar_length(string.len() + line_buffer.len(), start_mark)?;
                 c = self.raw_read_ch();
             }
 
             // Our last character read is stored in `c`. It is either an EOF or a break. In any
             // case, we need to push it back into `self.buffer` so it may be properly read
             // after. We must not insert it in `string`.
-            self.buffer.push_back(c).unwrap();
+            self.buffer
+                .push_back(c)
+                .map_err(|_| self.inconsistent_state())?;
 
             // We need to manually update our position; we haven't called a `skip` function.
-            self.mark.col += line_buffer.len();
-            self.mark.index += line_buffer.len();
+            let chars = line_buffer.chars().count();
+            self.mark.col += chars;
+            self.mark.index += chars;
+            self.mark.byte_offset += line_buffer.len();
 
             // We can now append our bytes to our `string`.
-            string.reserve(line_buffer.as_bytes().len());
+            string.reserve(line_buffer.len());
             string.push_str(line_buffer);
             // This clears the _contents_ without touching the _capacity_.
             line_buffer.clear();
         }
+        Ok(())
     }
 
     /// Skip the block scalar indentation and empty lines.
-    fn skip_block_scalar_indent(&mut self, indent: usize, breaks: &mut String) {
+    ///
+    /// # Errors
+    /// Return an error if the line breaks of the empty lines, after the `buffered` bytes already
+    /// buffered for the scalar starting at `start_mark`, exceed [`Limits::max_scalar_bytes`].
+    fn skip_block_scalar_indent(
+        &mut self,
+        indent: usize,
+        breaks: &mut String,
+        buffered: usize,
+        start_mark: Marker,
+    ) -> ScanResult {
         loop {
             // Consume all spaces. Tabs cannot be used as indentation.
             if indent < BUFFER_LEN - 2 {
@@ -1861,20 +3279,30 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
 
             // If our current line is empty, skip over the break and continue looping.
-            if is_break(self.ch()) {
+            if self.is_break(self.ch()) {
                 self.read_break(breaks);
+                self.check_scalar_length(buffered + breaks.len(), start_mark)?;
             } else {
                 // Otherwise, we have a content line. Return control.
                 break;
             }
         }
+        Ok(())
     }
 
     /// Determine the indentation level for a block scalar from the first line of its contents.
     ///
     /// The function skips over whitespace-only lines and sets `indent` to the the longest
     /// whitespace line that was encountered.
-    fn skip_block_scalar_first_line_indent(&mut self, indent: &mut usize, breaks: &mut String) {
+    ///
+    /// # Errors
+    /// Return an error if the line breaks of the empty lines exceed [`Limits::max_scalar_bytes`].
+    fn skip_block_scalar_first_line_indent(
+        &mut self,
+        indent: &mut usize,
+        breaks: &mut String,
+        start_mark: Marker,
+    ) -> ScanResult {
         let mut max_indent = 0;
         loop {
             // Consume all spaces. Tabs cannot be used as indentation.
@@ -1886,10 +3314,11 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 max_indent = self.mark.col;
             }
 
-            if is_break(self.ch()) {
+            if self.is_break(self.ch()) {
                 // If our current line is empty, skip over the break and continue looping.
                 self.lookahead(2);
                 self.read_break(breaks);
+                self.check_scalar_length(breaks.len(), start_mark)?;
             } else {
                 // Otherwise, we have a content line. Return control.
                 break;
@@ -1908,35 +3337,40 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         if self.indent > 0 {
             *indent = (*indent).max(1);
         }
+        Ok(())
     }
 
     fn fetch_flow_scalar(&mut self, single: bool) -> ScanResult {
-        self.save_simple_key();
+        self.save_simple_key()?;
         self.disallow_simple_key();
 
-        let tok = self.scan_flow_scalar(single)?;
+        let start_mark = self.mark;
+        let tok = self
+            .scan_flow_scalar(single)
+            .map_err(|e| e.with_context(ErrorContext::QuotedScalar, start_mark))?;
 
         // From spec: To ensure JSON compatibility, if a key inside a flow mapping is JSON-like,
         // YAML allows the following value to be specified adjacent to the “:”.
         self.skip_to_next_token()?;
         self.adjacent_value_allowed_at = self.mark.index;
 
-        self.tokens.push_back(tok);
+        self.push_token(tok);
         Ok(())
     }
 
     #[allow(clippy::too_many_lines)]
-    fn scan_flow_scalar(&mut self, single: bool) -> Result<Token, ScanError> {
+    fn scan_flow_scalar(&mut self, single: bool) -> Result<Token<'input>, ScanError> {
         let start_mark = self.mark;
 
-        let mut string = String::new();
-        let mut leading_break = String::new();
-        let mut trailing_breaks = String::new();
-        let mut whitespaces = String::new();
+        let mut string = self.take_scalar_buffer();
+        let mut leading_break = take_cleared(&mut self.buf_leading_break);
+        let mut trailing_breaks = take_cleared(&mut self.buf_trailing_breaks);
+        let mut whitespaces = take_cleared(&mut self.buf_whitespaces);
         let mut leading_blanks;
 
         /* Eat the left quote. */
         self.skip_non_blank();
+        let start_offset = self.mark.byte_offset;
 
         loop {
             /* Check for a document indicator. */
@@ -1947,25 +3381,25 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     || ((self.buffer[0] == '.')
                         && (self.buffer[1] == '.')
                         && (self.buffer[2] == '.')))
-                && is_blank_or_breakz(self.buffer[3])
+                && self.is_blank_or_breakz(self.buffer[3])
             {
-                return Err(ScanError::new_str(
-                    start_mark,
-                    "while scanning a quoted scalar, found unexpected document indicator",
+                return Err(ScanError::from_kind(
+                    self.mark,
+                    ScanErrorKind::QuotedScalarDocumentIndicator,
                 ));
             }
 
             if is_z(self.ch()) {
-                return Err(ScanError::new_str(
-                    start_mark,
-                    "while scanning a quoted scalar, found unexpected end of stream",
+                return Err(ScanError::from_kind(
+                    self.mark,
+                    ScanErrorKind::UnterminatedQuotedScalar,
                 ));
             }
 
             if (self.mark.col as isize) < self.indent {
-                return Err(ScanError::new_str(
-                    start_mark,
-                    "invalid indentation in quoted scalar",
+                return Err(ScanError::from_kind(
+                    self.mark,
+                    ScanErrorKind::QuotedScalarIndentation,
                 ));
             }
 
@@ -1974,7 +3408,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 single,
                 &mut string,
                 &mut leading_blanks,
-                &start_mark,
+                start_mark,
             )?;
 
             match self.look_ch() {
@@ -1984,15 +3418,24 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
 
             // Consume blank characters.
-            while is_blank(self.ch()) || is_break(self.ch()) {
+            while is_blank(self.ch()) || self.is_break(self.ch()) {
                 if is_blank(self.ch()) {
                     // Consume a space or a tab character.
                     if leading_blanks {
                         if self.ch() == '\t' && (self.mark.col as isize) < self.indent {
-                            return Err(ScanError::new_str(
+                            self.accept_leniently(
                                 self.mark,
-                                "tab cannot be used as indentation",
-                            ));
+                                WarningKind::TabIndentation,
+                                ScanError::from_kind(
+                                    self.mark,
+                                    ScanErrorKind::QuotedScalarTabIndentation,
+                                ),
+                            )?;
+                            // Skip the rest of the indentation so that it is reported once.
+                            while is_blank(self.look_ch()) {
+                                self.skip_blank();
+                            }
+                            continue;
                         }
                         self.skip_blank();
                     } else {
@@ -2010,17 +3453,15 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         leading_blanks = true;
                     }
                 }
+                let buffered =
+                    string.len() + whitespaces.len() + leading_break.len() + trailing_breaks.len();
+                self.check_scalar_length(buffered, start_mark)?;
                 self.lookahead(1);
             }
 
             // Join the whitespaces or fold line breaks.
             if leading_blanks {
-                if leading_break.is_empty() {
-                    string.push_str(&leading_break);
-                    string.push_str(&trailing_breaks);
-                    trailing_breaks.clear();
-                    leading_break.clear();
-                } else {
+                if leading_break == "\n" {
                     if trailing_breaks.is_empty() {
                         string.push(' ');
                     } else {
@@ -2028,6 +3469,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         trailing_breaks.clear();
                     }
                     leading_break.clear();
+                } else {
+                    // YAML 1.1 line and paragraph separators are not folded.
+                    string.push_str(&leading_break);
+                    string.push_str(&trailing_breaks);
+                    trailing_breaks.clear();
+                    leading_break.clear();
                 }
             } else {
                 string.push_str(&whitespaces);
@@ -2037,22 +3484,23 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         // Eat the right quote.
         self.skip_non_blank();
+        let end_mark = self.mark;
         // Ensure there is no invalid trailing content.
         self.skip_ws_to_eol(SkipTabs::Yes)?;
         match self.ch() {
             // These can be encountered in flow sequences or mappings.
             ',' | '}' | ']' if self.flow_level > 0 => {}
             // An end-of-line / end-of-stream is fine. No trailing content.
-            c if is_breakz(c) => {}
+            c if self.is_breakz(c) => {}
             // ':' can be encountered if our scalar is a key.
             // Outside of flow contexts, keys cannot span multiple lines
             ':' if self.flow_level == 0 && start_mark.line == self.mark.line => {}
             // Inside a flow context, this is allowed.
             ':' if self.flow_level > 0 => {}
             _ => {
-                return Err(ScanError::new_str(
+                return Err(ScanError::from_kind(
                     self.mark,
-                    "invalid trailing content after double-quoted scalar",
+                    ScanErrorKind::QuotedScalarTrailingContent,
                 ));
             }
         }
@@ -2062,7 +3510,14 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         } else {
             TScalarStyle::DoubleQuoted
         };
-        Ok(Token(start_mark, TokenType::Scalar(style, string)))
+        self.buf_leading_break = leading_break;
+        self.buf_trailing_breaks = trailing_breaks;
+        self.buf_whitespaces = whitespaces;
+        let value = self.scalar_value(start_offset, string);
+        Ok(Token(
+            Span::new(start_mark, end_mark),
+            TokenType::Scalar(style, value),
+        ))
     }
 
     /// Consume successive non-whitespace characters from a flow scalar.
@@ -2072,16 +3527,17 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     /// for double quoted scalars).
     ///
     /// # Errors
-    /// Return an error if an invalid escape sequence is found.
+    /// Return an error if an invalid escape sequence is found, or if the scalar starting at
+    /// `start_mark` exceeds [`Limits::max_scalar_bytes`].
     fn consume_flow_scalar_non_whitespace_chars(
         &mut self,
         single: bool,
         string: &mut String,
         leading_blanks: &mut bool,
-        start_mark: &Marker,
+        start_mark: Marker,
     ) -> Result<(), ScanError> {
         self.lookahead(2);
-        while !is_blank_or_breakz(self.ch()) {
+        while !self.is_blank_or_breakz(self.ch()) {
             match self.ch() {
                 // Check for an escaped single quote.
                 '\'' if self.buffer[1] == '\'' && single => {
@@ -2092,7 +3548,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 '\'' if single => break,
                 '"' if !single => break,
                 // Check for an escaped line break.
-                '\\' if !single && is_break(self.buffer[1]) => {
+                '\\' if !single && self.is_break(self.buffer[1]) => {
                     self.lookahead(3);
                     self.skip_non_blank();
                     self.skip_linebreak();
@@ -2101,13 +3557,14 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 }
                 // Check for an escape sequence.
                 '\\' if !single => {
-                    string.push(self.resolve_flow_scalar_escape_sequence(start_mark)?);
+                    string.push(self.resolve_flow_scalar_escape_sequence()?);
                 }
                 c => {
                     string.push(c);
                     self.skip_non_blank();
                 }
             }
+            self.check_scalar_length(string.len(), start_mark)?;
             self.lookahead(2);
         }
         Ok(())
@@ -2119,10 +3576,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     ///
     /// # Errors
     /// Return an error if an invalid escape sequence is found.
-    fn resolve_flow_scalar_escape_sequence(
-        &mut self,
-        start_mark: &Marker,
-    ) -> Result<char, ScanError> {
+    fn resolve_flow_scalar_escape_sequence(&mut self) -> Result<char, ScanError> {
         let mut code_length = 0usize;
         let mut ret = '\0';
 
@@ -2138,7 +3592,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             'e' => ret = '\x1b',
             ' ' => ret = '\x20',
             '"' => ret = '"',
-            '/' => ret = '/',
+            '/' if self.version != YamlVersion::V1_1 => ret = '/',
             '\\' => ret = '\\',
             // Unicode next line (#x85)
             'N' => ret = char::from_u32(0x85).unwrap(),
@@ -2152,9 +3606,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             'u' => code_length = 4,
             'U' => code_length = 8,
             _ => {
-                return Err(ScanError::new_str(
-                    *start_mark,
-                    "while parsing a quoted scalar, found unknown escape character",
+                return Err(ScanError::from_kind(
+                    self.mark,
+                    ScanErrorKind::UnknownEscape(self.buffer[1]),
                 ))
             }
         }
@@ -2166,18 +3620,18 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             let mut value = 0u32;
             for i in 0..code_length {
                 if !is_hex(self.buffer[i]) {
-                    return Err(ScanError::new_str(
-                        *start_mark,
-                        "while parsing a quoted scalar, did not find expected hexadecimal number",
+                    return Err(ScanError::from_kind(
+                        self.mark,
+                        ScanErrorKind::InvalidHexEscape,
                     ));
                 }
                 value = (value << 4) + as_hex(self.buffer[i]);
             }
 
             let Some(ch) = char::from_u32(value) else {
-                return Err(ScanError::new_str(
-                    *start_mark,
-                    "while parsing a quoted scalar, found invalid Unicode character escape code",
+                return Err(ScanError::from_kind(
+                    self.mark,
+                    ScanErrorKind::InvalidUnicodeEscape(value),
                 ));
             };
             ret = ch;
@@ -2187,13 +3641,36 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         Ok(ret)
     }
 
+    /// Take [`Self::buf_scalar`], cleared, to accumulate the contents of a scalar into.
+    ///
+    /// The buffer is given back by [`Self::scalar_value`].
+    fn take_scalar_buffer(&mut self) -> String {
+        take_cleared(&mut self.buf_scalar)
+    }
+
+    /// Turn the contents of a scalar into its value.
+    ///
+    /// `start_offset` is the byte offset in the input at which the contents of the scalar begin.
+    /// If the contents appear verbatim in the input from there (no escape sequence or line
+    /// folding was involved), and the input can be sliced, the value is borrowed from the input
+    /// and `string` is kept for the next scalar. Otherwise, `string` is the value.
+    fn scalar_value(&mut self, start_offset: usize, string: String) -> Cow<'input, str> {
+        match self.rdr.slice(start_offset, start_offset + string.len()) {
+            Some(slice) if slice == string => {
+                self.buf_scalar = string;
+                Cow::Borrowed(slice)
+            }
+            _ => Cow::Owned(string),
+        }
+    }
+
     fn fetch_plain_scalar(&mut self) -> ScanResult {
-        self.save_simple_key();
+        self.save_simple_key()?;
         self.disallow_simple_key();
 
         let tok = self.scan_plain_scalar()?;
 
-        self.tokens.push_back(tok);
+        self.push_token(tok);
         Ok(())
     }
 
@@ -2202,44 +3679,44 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     /// Plain scalars are the most readable but restricted style. They may span multiple lines in
     /// some contexts.
     #[allow(clippy::too_many_lines)]
-    fn scan_plain_scalar(&mut self) -> Result<Token, ScanError> {
+    fn scan_plain_scalar(&mut self) -> Result<Token<'input>, ScanError> {
         self.unroll_non_block_indents();
         let indent = self.indent + 1;
         let start_mark = self.mark;
 
         if self.flow_level > 0 && (start_mark.col as isize) < indent {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 start_mark,
-                "invalid indentation in flow construct",
+                ScanErrorKind::FlowIndentation,
             ));
         }
 
-        let mut string = String::with_capacity(32);
-        let mut leading_break = String::with_capacity(32);
-        let mut trailing_breaks = String::with_capacity(32);
-        let mut whitespaces = String::with_capacity(32);
+        let start_offset = self.mark.byte_offset;
+        let mut end_mark = self.mark;
+        let mut string = self.take_scalar_buffer();
+        let mut leading_break = take_cleared(&mut self.buf_leading_break);
+        let mut trailing_breaks = take_cleared(&mut self.buf_trailing_breaks);
+        let mut whitespaces = take_cleared(&mut self.buf_whitespaces);
 
         loop {
             self.lookahead(4);
-            if self.next_is_document_indicator() || self.ch() == '#' {
+            if self.next_is_document_indicator()
+                || self.ch() == '#'
+                || self.next_is_bom_and_document_start()
+            {
                 break;
             }
 
             if self.flow_level > 0 && self.ch() == '-' && is_flow(self.buffer[1]) {
-                return Err(ScanError::new_str(
+                return Err(ScanError::from_kind(
                     self.mark,
-                    "plain scalar cannot start with '-' followed by ,[]{}",
+                    ScanErrorKind::PlainScalarDashFlowIndicator,
                 ));
             }
 
-            if !is_blank_or_breakz(self.ch()) && self.next_can_be_plain_scalar() {
+            if !self.is_blank_or_breakz(self.ch()) && self.next_can_be_plain_scalar() {
                 if self.leading_whitespace {
-                    if leading_break.is_empty() {
-                        string.push_str(&leading_break);
-                        string.push_str(&trailing_breaks);
-                        trailing_breaks.clear();
-                        leading_break.clear();
-                    } else {
+                    if leading_break == "\n" {
                         if trailing_breaks.is_empty() {
                             string.push(' ');
                         } else {
@@ -2247,6 +3724,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                             trailing_breaks.clear();
                         }
                         leading_break.clear();
+                    } else {
+                        // YAML 1.1 line and paragraph separators are not folded.
+                        string.push_str(&leading_break);
+                        string.push_str(&trailing_breaks);
+                        trailing_breaks.clear();
+                        leading_break.clear();
                     }
                     self.leading_whitespace = false;
                 } else if !whitespaces.is_empty() {
@@ -2260,27 +3743,30 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 self.lookahead(2);
 
                 // Add content non-blank characters to the scalar.
-                while !is_blank_or_breakz(self.ch()) {
+                while !self.is_blank_or_breakz(self.ch()) {
                     if !self.next_can_be_plain_scalar() {
                         break;
                     }
 
                     string.push(self.ch());
+                    self.check_scalar_length(string.len(), start_mark)?;
                     self.skip_non_blank();
                     self.lookahead(2);
                 }
+                end_mark = self.mark;
+                self.check_scalar_length(string.len(), start_mark)?;
             }
 
             // We may reach the end of a plain scalar if:
             //  - We reach eof
             //  - We reach ": "
             //  - We find a flow character in a flow context
-            if !(is_blank(self.ch()) || is_break(self.ch())) {
+            if !(is_blank(self.ch()) || self.is_break(self.ch())) {
                 break;
             }
 
             // Process blank characters.
-            while is_blank(self.look_ch()) || is_break(self.ch()) {
+            while is_blank(self.look_ch()) || self.is_break(self.ch()) {
                 if is_blank(self.ch()) {
                     if !self.leading_whitespace {
                         whitespaces.push(self.ch());
@@ -2288,12 +3774,14 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     } else if (self.mark.col as isize) < indent && self.ch() == '\t' {
                         // Tabs in an indentation columns are allowed if and only if the line is
                         // empty. Skip to the end of the line.
+                        let tab_mark = self.mark;
                         self.skip_ws_to_eol(SkipTabs::Yes)?;
-                        if !is_breakz(self.ch()) {
-                            return Err(ScanError::new_str(
-                                start_mark,
-                                "while scanning a plain scalar, found a tab",
-                            ));
+                        if !self.is_breakz(self.ch()) {
+                            self.accept_leniently(
+                                tab_mark,
+                                WarningKind::TabIndentation,
+                                ScanError::from_kind(start_mark, ScanErrorKind::PlainScalarTab),
+                            )?;
                         }
                     } else {
                         self.skip_blank();
@@ -2309,6 +3797,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         self.leading_whitespace = true;
                     }
                 }
+                let buffered =
+                    string.len() + whitespaces.len() + leading_break.len() + trailing_breaks.len();
+                self.check_scalar_length(buffered, start_mark)?;
             }
 
             // check indentation level
@@ -2321,9 +3812,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.allow_simple_key();
         }
 
+        self.buf_leading_break = leading_break;
+        self.buf_trailing_breaks = trailing_breaks;
+        self.buf_whitespaces = whitespaces;
+        let value = self.scalar_value(start_offset, string);
         Ok(Token(
-            start_mark,
-            TokenType::Scalar(TScalarStyle::Plain, string),
+            Span::new(start_mark, end_mark),
+            TokenType::Scalar(TScalarStyle::Plain, value),
         ))
     }
 
@@ -2332,9 +3827,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         if self.flow_level == 0 {
             // Check if we are allowed to start a new key (not necessarily simple).
             if !self.simple_key_allowed {
-                return Err(ScanError::new_str(
+                return Err(ScanError::from_kind(
                     self.mark,
-                    "mapping keys are not allowed in this context",
+                    ScanErrorKind::MappingKeyNotAllowed,
                 ));
             }
             self.roll_indent(
@@ -2342,7 +3837,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 None,
                 TokenType::BlockMappingStart,
                 start_mark,
-            );
+                start_mark,
+            )?;
         } else {
             // The scanner, upon emitting a `Key`, will prepend a `MappingStart` event.
             self.flow_mapping_started = true;
@@ -2357,14 +3853,15 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         self.skip_non_blank();
+        let span = Span::new(start_mark, self.mark);
         self.skip_yaml_whitespace()?;
         if self.ch() == '\t' {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 self.mark(),
-                "tabs disallowed in this context",
+                ScanErrorKind::TabsNotAllowed,
             ));
         }
-        self.tokens.push_back(Token(start_mark, TokenType::Key));
+        self.push_token(Token(span, TokenType::Key));
         Ok(())
     }
 
@@ -2390,9 +3887,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         // flow character), but the ']' is not the value. The value is an invisible empty
         // space which is represented as null ('~').
         if self.mark.index != self.adjacent_value_allowed_at && (nc == '[' || nc == '{') {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 self.mark,
-                "':' may not precede any of `[{` in flow mapping",
+                ScanErrorKind::ColonBeforeFlowCollection,
             ));
         }
 
@@ -2401,64 +3898,79 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
     /// Fetch a value from a mapping (after a `:`).
     fn fetch_value(&mut self) -> ScanResult {
-        let sk = self.simple_keys.last().unwrap().clone();
+        let sk = self
+            .simple_keys
+            .last()
+            .ok_or_else(|| self.inconsistent_state())?
+            .clone();
         let start_mark = self.mark;
         let is_implicit_flow_mapping = self.flow_level > 0 && !self.flow_mapping_started;
         if is_implicit_flow_mapping {
-            *self.implicit_flow_mapping_states.last_mut().unwrap() = ImplicitMappingState::Inside;
+            if let Some(state) = self.implicit_flow_mapping_states.last_mut() {
+                *state = ImplicitMappingState::Inside;
+            }
         }
 
         // Skip over ':'.
         self.skip_non_blank();
+        let span = Span::new(start_mark, self.mark);
         if self.look_ch() == '\t'
             && !self.skip_ws_to_eol(SkipTabs::Yes)?.has_valid_yaml_ws()
             && (self.ch() == '-' || is_alpha(self.ch()))
         {
-            return Err(ScanError::new_str(
+            return Err(ScanError::from_kind(
                 self.mark,
-                "':' must be followed by a valid YAML whitespace",
+                ScanErrorKind::ColonExpectedWhitespace,
             ));
         }
 
         if sk.possible {
             // insert simple key
-            let tok = Token(sk.mark, TokenType::Key);
-            self.insert_token(sk.token_number - self.tokens_parsed, tok);
+            let tok = Token(Span::empty(sk.mark), TokenType::Key);
+            self.insert_token(sk.token_number, tok, sk.mark)?;
             if is_implicit_flow_mapping {
                 if sk.mark.line < start_mark.line {
-                    return Err(ScanError::new_str(
+                    return Err(ScanError::from_kind(
                         start_mark,
-                        "illegal placement of ':' indicator",
+                        ScanErrorKind::IllegalColonPlacement,
                     ));
                 }
+                // The parser reports the mapping after the `:`.
                 self.insert_token(
-                    sk.token_number - self.tokens_parsed,
-                    Token(self.mark, TokenType::FlowMappingStart),
-                );
+                    sk.token_number,
+                    Token(Span::empty(sk.mark), TokenType::FlowMappingStart),
+                    self.mark,
+                )?;
             }
 
-            // Add the BLOCK-MAPPING-START token if needed.
+            // Add the BLOCK-MAPPING-START token if needed. The parser reports it at the `:`.
             self.roll_indent(
                 sk.mark.col,
                 Some(sk.token_number),
                 TokenType::BlockMappingStart,
+                sk.mark,
                 start_mark,
-            );
+            )?;
             self.roll_one_col_indent();
 
-            self.simple_keys.last_mut().unwrap().possible = false;
+            match self.simple_keys.last_mut() {
+                Some(sk) => sk.possible = false,
+                None => return Err(self.inconsistent_state()),
+            }
             self.disallow_simple_key();
         } else {
             if is_implicit_flow_mapping {
-                self.tokens
-                    .push_back(Token(self.mark, TokenType::FlowMappingStart));
+                self.tokens.push_back((
+                    Token(Span::empty(start_mark), TokenType::FlowMappingStart),
+                    self.mark,
+                ));
             }
             // The ':' indicator follows a complex key.
             if self.flow_level == 0 {
                 if !self.simple_key_allowed {
-                    return Err(ScanError::new_str(
+                    return Err(ScanError::from_kind(
                         start_mark,
-                        "mapping values are not allowed in this context",
+                        ScanErrorKind::MappingValueNotAllowed,
                     ));
                 }
 
@@ -2467,7 +3979,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     None,
                     TokenType::BlockMappingStart,
                     start_mark,
-                );
+                    start_mark,
+                )?;
             }
             self.roll_one_col_indent();
 
@@ -2477,7 +3990,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 self.disallow_simple_key();
             }
         }
-        self.tokens.push_back(Token(start_mark, TokenType::Value));
+        self.push_token(Token(span, TokenType::Value));
 
         Ok(())
     }
@@ -2487,9 +4000,18 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     /// An indentation level is added only if:
     ///   - We are not in a flow-style construct (which don't have indentation per-se).
     ///   - The current column is further indented than the last indent we have registered.
-    fn roll_indent(&mut self, col: usize, number: Option<usize>, tok: TokenType, mark: Marker) {
+    ///
+    /// The token is given an empty span at `mark` and is reported by the parser at `marker`.
+    fn roll_indent(
+        &mut self,
+        col: usize,
+        number: Option<usize>,
+        tok: TokenType<'input>,
+        mark: Marker,
+        marker: Marker,
+    ) -> ScanResult {
         if self.flow_level > 0 {
-            return;
+            return Ok(());
         }
 
         // If the last indent was a non-block indent, remove it.
@@ -2510,12 +4032,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 needs_block_end: true,
             });
             self.indent = col as isize;
-            let tokens_parsed = self.tokens_parsed;
             match number {
-                Some(n) => self.insert_token(n - tokens_parsed, Token(mark, tok)),
-                None => self.tokens.push_back(Token(mark, tok)),
+                Some(n) => self.insert_token(n, Token(Span::empty(mark), tok), marker)?,
+                None => self.tokens.push_back((Token(Span::empty(mark), tok), marker)),
             }
         }
+        Ok(())
     }
 
     /// Pop indentation levels from the stack as much as needed.
@@ -2523,17 +4045,18 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     /// Indentation levels are popped from the stack while they are further indented than `col`.
     /// If we are in a flow-style construct (which don't have indentation per-se), this function
     /// does nothing.
-    fn unroll_indent(&mut self, col: isize) {
+    fn unroll_indent(&mut self, col: isize) -> ScanResult {
         if self.flow_level > 0 {
-            return;
+            return Ok(());
         }
         while self.indent > col {
-            let indent = self.indents.pop().unwrap();
+            let indent = self.indents.pop().ok_or_else(|| self.inconsistent_state())?;
             self.indent = indent.indent;
             if indent.needs_block_end {
-                self.tokens.push_back(Token(self.mark, TokenType::BlockEnd));
+                self.push_token(Token(Span::empty(self.mark), TokenType::BlockEnd));
             }
         }
+        Ok(())
     }
 
     /// Add an indentation level of 1 column that does not start a block.
@@ -2542,7 +4065,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     /// An indentation is not added if we are inside a flow level or if the last indent is already
     /// a non-block indent.
     fn roll_one_col_indent(&mut self) {
-        if self.flow_level == 0 && self.indents.last().map_or(false, |x| x.needs_block_end) {
+        if self.flow_level == 0 && self.indents.last().is_some_and(|x| x.needs_block_end) {
             self.indents.push(Indent {
                 indent: self.indent,
                 needs_block_end: false,
@@ -2563,11 +4086,15 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     }
 
     /// Mark the next token to be inserted as a potential simple key.
-    fn save_simple_key(&mut self) {
+    fn save_simple_key(&mut self) -> ScanResult {
         if self.simple_key_allowed {
             let required = self.flow_level == 0
                 && self.indent == (self.mark.col as isize)
-                && self.indents.last().unwrap().needs_block_end;
+                && self
+                    .indents
+                    .last()
+                    .ok_or_else(|| self.inconsistent_state())?
+                    .needs_block_end;
             let mut sk = SimpleKey::new(self.mark);
             sk.possible = true;
             sk.required = required;
@@ -2576,12 +4103,18 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.simple_keys.pop();
             self.simple_keys.push(sk);
         }
+        Ok(())
     }
 
     fn remove_simple_key(&mut self) -> ScanResult {
-        let last = self.simple_keys.last_mut().unwrap();
+        let Some(last) = self.simple_keys.last_mut() else {
+            return Err(self.inconsistent_state());
+        };
         if last.possible && last.required {
-            return Err(ScanError::new_str(self.mark, "simple key expected"));
+            return Err(ScanError::from_kind(
+                self.mark,
+                ScanErrorKind::SimpleKeyExpected,
+            ));
         }
 
         last.possible = false;
@@ -2597,7 +4130,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn next_can_be_plain_scalar(&self) -> bool {
         match self.ch() {
             // indicators can end a plain scalar, see 7.3.3. Plain Style
-            ':' if is_blank_or_breakz(self.buffer[1])
+            ':' if self.is_blank_or_breakz(self.buffer[1])
                 || (self.flow_level > 0 && is_flow(self.buffer[1])) =>
             {
                 false
@@ -2622,8 +4155,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             if *implicit_mapping == ImplicitMappingState::Inside {
                 self.flow_mapping_started = false;
                 *implicit_mapping = ImplicitMappingState::Possible;
-                self.tokens
-                    .push_back(Token(mark, TokenType::FlowMappingEnd));
+                self.push_token(Token(Span::empty(mark), TokenType::FlowMappingEnd));
             }
         }
     }
@@ -2666,7 +4198,7 @@ impl SkipTabs {
 /// Chomping, how final line breaks and trailing empty lines are interpreted.
 ///
 /// See YAML spec 8.1.1.2.
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
 pub enum Chomping {
     /// The final line break and any trailing empty lines are excluded.
     Strip,
@@ -2676,6 +4208,16 @@ pub enum Chomping {
     Keep,
 }
 
+/// Take the contents of `buffer`, leaving it empty, and clear them.
+///
+/// This allows reusing the capacity of `buffer` while it is borrowed mutably alongside `self`.
+#[inline]
+fn take_cleared(buffer: &mut String) -> String {
+    let mut taken = std::mem::take(buffer);
+    taken.clear();
+    taken
+}
+
 #[cfg(test)]
 mod test {
     #[test]
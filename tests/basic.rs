@@ -1,13 +1,20 @@
 #![allow(clippy::bool_assert_comparison)]
 #![allow(clippy::float_cmp)]
 
-use saphyr_parser::{Event, Parser, ScanError, TScalarStyle};
+use std::{borrow::Cow, io::Read};
+
+use saphyr_parser::{
+    scanner::{Marker, Scanner, Span, TEncoding, TokenType},
+    BlockScalarHeader, Chomping, CollectionStyle, CommentStyle, Diagnostic, ErrorContext, Event,
+    EventReceiver, Limits, Parser, ParserOptions, ScanError, ScanErrorKind, SpannedEventReceiver,
+    StrInput, TScalarStyle, Tag, TokenKind, WarningKind, YamlVersion,
+};
 
 /// Run the parser through the string.
 ///
 /// # Returns
 /// This functions returns the events if parsing succeeds, the error the parser returned otherwise.
-fn run_parser(input: &str) -> Result<Vec<Event>, ScanError> {
+fn run_parser(input: &str) -> Result<Vec<Event<'_>>, ScanError> {
     let mut events = vec![];
     for x in Parser::new_from_str(input) {
         events.push(x?.0);
@@ -15,6 +22,62 @@ fn run_parser(input: &str) -> Result<Vec<Event>, ScanError> {
     Ok(events)
 }
 
+/// Run the parser through the bytes, reading them from a [`Read`]er.
+///
+/// # Returns
+/// This functions returns the events if parsing succeeds, the error the parser returned otherwise.
+fn run_parser_from_reader<R: Read>(input: R) -> Result<Vec<Event<'static>>, ScanError> {
+    let mut events = vec![];
+    for x in Parser::new_from_reader(input) {
+        events.push(x?.0);
+    }
+    Ok(events)
+}
+
+/// A receiver collecting the events it receives, along with their spans.
+#[derive(Default)]
+struct SpanCollector<'input> {
+    events: Vec<(Event<'input>, Span)>,
+}
+
+impl<'input> SpannedEventReceiver<'input> for SpanCollector<'input> {
+    fn on_event(&mut self, ev: Event<'input>, span: Span) {
+        self.events.push((ev, span));
+    }
+}
+
+/// Load the string with the given options, returning the events along with their spans.
+fn run_parser_spanned(input: &str, options: ParserOptions) -> Vec<(Event<'_>, Span)> {
+    let mut collector = SpanCollector::default();
+    Parser::new_from_str(input)
+        .options(options)
+        .load(&mut collector, true)
+        .unwrap();
+    collector.events
+}
+
+/// A reader returning its input one byte at a time, then failing if `error` is set.
+struct TrickleReader<'a> {
+    input: &'a [u8],
+    error: Option<std::io::ErrorKind>,
+}
+
+impl Read for TrickleReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.input.split_first() {
+            Some((byte, rest)) if !buf.is_empty() => {
+                buf[0] = *byte;
+                self.input = rest;
+                Ok(1)
+            }
+            _ => match self.error {
+                Some(kind) => Err(std::io::Error::new(kind, "trickle reader failed")),
+                None => Ok(0),
+            },
+        }
+    }
+}
+
 #[test]
 fn test_fail() {
     let s = "
@@ -32,6 +95,28 @@ key1:a2
         error.to_string(),
         "mapping values are not allowed in this context at byte 26 line 4 column 4"
     );
+    assert_eq!(error.kind(), &ScanErrorKind::MappingValueNotAllowed);
+}
+
+#[test]
+fn test_error_kinds() {
+    let kind = |s| run_parser(s).unwrap_err().kind().clone();
+    assert_eq!(kind("a: @b"), ScanErrorKind::UnexpectedCharacter('@'));
+    assert_eq!(kind("\"\\q\""), ScanErrorKind::UnknownEscape('q'));
+    assert_eq!(kind("- *a"), ScanErrorKind::UnknownAnchor("a".into()));
+    assert_eq!(
+        kind("!e!x y"),
+        ScanErrorKind::UndeclaredTagHandle("!e!".into())
+    );
+    assert_eq!(
+        kind("%TAG !e! a\n%TAG !e! b\n---\n"),
+        ScanErrorKind::DuplicateTagDirective("!e!".into())
+    );
+    assert_eq!(kind("[a, b"), ScanErrorKind::ExpectedFlowSequenceSeparator);
+
+    let error = ScanError::new_str(saphyr_parser::Marker::default(), "custom");
+    assert_eq!(error.kind(), &ScanErrorKind::Custom);
+    assert_eq!(error.info(), "custom");
 }
 
 #[test]
@@ -45,9 +130,9 @@ fn test_empty_doc() {
         run_parser("---").unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::Scalar("~".to_string(), TScalarStyle::Plain, 0, None),
-            Event::DocumentEnd,
+            Event::DocumentStart(true, None, vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("~".into(), TScalarStyle::Plain, 0, None),
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -59,12 +144,12 @@ fn test_utf() {
         run_parser("a: 你好").unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::MappingStart(0, None),
-            Event::Scalar("a".to_string(), TScalarStyle::Plain, 0, None),
-            Event::Scalar("你好".to_string(), TScalarStyle::Plain, 0, None),
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::MappingStart(0, None, CollectionStyle::Block),
+            Event::Scalar("a".into(), TScalarStyle::Plain, 0, None),
+            Event::Scalar("你好".into(), TScalarStyle::Plain, 0, None),
             Event::MappingEnd,
-            Event::DocumentEnd,
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -83,12 +168,12 @@ a: b # This is another comment
         run_parser(s).unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::MappingStart(0, None),
-            Event::Scalar("a".to_string(), TScalarStyle::Plain, 0, None),
-            Event::Scalar("b".to_string(), TScalarStyle::Plain, 0, None),
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::MappingStart(0, None, CollectionStyle::Block),
+            Event::Scalar("a".into(), TScalarStyle::Plain, 0, None),
+            Event::Scalar("b".into(), TScalarStyle::Plain, 0, None),
             Event::MappingEnd,
-            Event::DocumentEnd,
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -106,13 +191,13 @@ fn test_quoting() {
         run_parser(s).unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::SequenceStart(0, None),
-            Event::Scalar("plain".to_string(), TScalarStyle::Plain, 0, None),
-            Event::Scalar("squote".to_string(), TScalarStyle::SingleQuoted, 0, None),
-            Event::Scalar("dquote".to_string(), TScalarStyle::DoubleQuoted, 0, None),
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::SequenceStart(0, None, CollectionStyle::Block),
+            Event::Scalar("plain".into(), TScalarStyle::Plain, 0, None),
+            Event::Scalar("squote".into(), TScalarStyle::SingleQuoted, 0, None),
+            Event::Scalar("dquote".into(), TScalarStyle::DoubleQuoted, 0, None),
             Event::SequenceEnd,
-            Event::DocumentEnd,
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -131,15 +216,15 @@ a scalar
         run_parser(s).unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::Scalar("a scalar".to_string(), TScalarStyle::Plain, 0, None),
-            Event::DocumentEnd,
-            Event::DocumentStart,
-            Event::Scalar("a scalar".to_string(), TScalarStyle::Plain, 0, None),
-            Event::DocumentEnd,
-            Event::DocumentStart,
-            Event::Scalar("a scalar".to_string(), TScalarStyle::Plain, 0, None),
-            Event::DocumentEnd,
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("a scalar".into(), TScalarStyle::Plain, 0, None),
+            Event::DocumentEnd(false),
+            Event::DocumentStart(true, None, vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("a scalar".into(), TScalarStyle::Plain, 0, None),
+            Event::DocumentEnd(false),
+            Event::DocumentStart(true, None, vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("a scalar".into(), TScalarStyle::Plain, 0, None),
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -152,9 +237,9 @@ fn test_github_27() {
         run_parser("&a").unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::Scalar(String::new(), TScalarStyle::Plain, 1, None),
-            Event::DocumentEnd,
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("~".into(), TScalarStyle::Plain, 1, None),
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -194,9 +279,9 @@ foobar";
         run_parser(s).unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::Scalar("foobar".to_string(), TScalarStyle::Plain, 0, None),
-            Event::DocumentEnd,
+            Event::DocumentStart(true, Some((1, 2)), vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("foobar".into(), TScalarStyle::Plain, 0, None),
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -209,9 +294,9 @@ fn test_bad_docstart() {
         run_parser("----").unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::Scalar("----".to_string(), TScalarStyle::Plain, 0, None),
-            Event::DocumentEnd,
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("----".into(), TScalarStyle::Plain, 0, None),
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -220,9 +305,9 @@ fn test_bad_docstart() {
         run_parser("--- #comment").unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::Scalar("~".to_string(), TScalarStyle::Plain, 0, None),
-            Event::DocumentEnd,
+            Event::DocumentStart(true, None, vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("~".into(), TScalarStyle::Plain, 0, None),
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -231,9 +316,9 @@ fn test_bad_docstart() {
         run_parser("---- #comment").unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::Scalar("----".to_string(), TScalarStyle::Plain, 0, None),
-            Event::DocumentEnd,
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("----".into(), TScalarStyle::Plain, 0, None),
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -300,3 +385,1184 @@ fn test_recursion_depth_check_arrays() {
     let s = "[".repeat(10_000) + &"]".repeat(10_000);
     assert!(run_parser(&s).is_err());
 }
+
+/// Counts the events it receives and the maximum depth it reaches.
+#[derive(Default)]
+struct DepthCounter {
+    events: usize,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl EventReceiver<'_> for DepthCounter {
+    fn on_event(&mut self, ev: Event) {
+        self.events += 1;
+        match ev {
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                self.depth += 1;
+                self.max_depth = self.max_depth.max(self.depth);
+            }
+            Event::SequenceEnd | Event::MappingEnd => self.depth -= 1,
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_load_deep_nesting() {
+    let s = "- ".repeat(200_000) + "a";
+    let mut counter = DepthCounter::default();
+    Parser::new_from_str(&s).load(&mut counter, true).unwrap();
+    assert_eq!(counter.max_depth, 200_000);
+    assert_eq!(counter.depth, 0);
+    assert_eq!(counter.events, 2 * 200_000 + 5);
+}
+
+#[test]
+fn test_mismatched_flow_brackets() {
+    for s in ["[: [[{]]]:", "[{]: a", "{[}: a", "[a: {b]: c"] {
+        assert!(run_parser(s).is_err(), "{s:?}");
+        let mut counter = DepthCounter::default();
+        assert!(Parser::new_from_str(s).load(&mut counter, true).is_err());
+        let mut parser =
+            Parser::new_from_str(s).options(ParserOptions::default().recover_from_errors(true));
+        parser.load(&mut counter, true).unwrap();
+        assert!(!parser.errors().is_empty());
+    }
+}
+
+#[test]
+fn test_reader() {
+    let s = "
+a: 你好
+b: [\"🦀\", 'é']
+c: |
+  ünïcödé
+";
+    let expected = run_parser(s).unwrap();
+    assert_eq!(run_parser_from_reader(s.as_bytes()).unwrap(), expected);
+    // Split multi-byte sequences across reads.
+    let reader = TrickleReader {
+        input: s.as_bytes(),
+        error: None,
+    };
+    assert_eq!(run_parser_from_reader(reader).unwrap(), expected);
+
+    // Inputs larger than the internal buffer of the reader.
+    let s = "- foo: bär\n".repeat(10_000);
+    assert_eq!(
+        run_parser_from_reader(s.as_bytes()).unwrap(),
+        run_parser(&s).unwrap()
+    );
+}
+
+#[test]
+fn test_reader_invalid_utf8() {
+    let Err(error) = run_parser_from_reader(&b"a: b\nc: d\xFFe\n"[..]) else {
+        panic!()
+    };
+    assert_eq!(error.info(), "invalid UTF-8 sequence in input");
+    assert_eq!(
+        (
+            error.marker().index(),
+            error.marker().line(),
+            error.marker().col()
+        ),
+        (9, 2, 4)
+    );
+
+    // The error must not be hidden by an unexpected end of input.
+    let Err(error) = run_parser_from_reader(&b"key: \"val\xC3"[..]) else {
+        panic!()
+    };
+    assert_eq!(error.info(), "invalid UTF-8 sequence in input");
+    assert_eq!(error.marker().col(), 9);
+}
+
+#[test]
+fn test_reader_io_error() {
+    let reader = TrickleReader {
+        input: b"a: b\n",
+        error: Some(std::io::ErrorKind::ConnectionReset),
+    };
+    let Err(error) = run_parser_from_reader(reader) else {
+        panic!()
+    };
+    assert_eq!(
+        error.info(),
+        "error while reading input: trickle reader failed"
+    );
+    assert_eq!(
+        error.kind(),
+        &ScanErrorKind::Io(std::io::ErrorKind::ConnectionReset)
+    );
+    assert_eq!(error.marker().line(), 2);
+
+    // The error of the reader is kept as the source of the scan error.
+    let source = std::error::Error::source(&error).unwrap();
+    let source = source.downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(source.kind(), std::io::ErrorKind::ConnectionReset);
+    assert_eq!(source.to_string(), "trickle reader failed");
+    // Clones share it.
+    assert!(std::error::Error::source(&error.clone()).is_some());
+}
+
+/// Encode the string in the given encoding.
+fn encode(s: &str, encoding: TEncoding) -> Vec<u8> {
+    match encoding {
+        TEncoding::Utf8 => s.as_bytes().to_vec(),
+        TEncoding::Utf16Le => s.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+        TEncoding::Utf16Be => s.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        TEncoding::Utf32Le => s.chars().flat_map(|c| u32::from(c).to_le_bytes()).collect(),
+        TEncoding::Utf32Be => s.chars().flat_map(|c| u32::from(c).to_be_bytes()).collect(),
+    }
+}
+
+#[test]
+fn test_reader_encodings() {
+    let s = "a: 你好\nb: [\"🦀\", 'é']\n";
+    let expected = run_parser(s).unwrap();
+    let encodings = [
+        TEncoding::Utf8,
+        TEncoding::Utf16Le,
+        TEncoding::Utf16Be,
+        TEncoding::Utf32Le,
+        TEncoding::Utf32Be,
+    ];
+    for encoding in encodings {
+        for input in [s.to_owned(), format!("\u{FEFF}{s}")] {
+            let bytes = encode(&input, encoding);
+            assert_eq!(
+                run_parser_from_reader(&bytes[..]).unwrap(),
+                expected,
+                "{encoding}"
+            );
+            let reader = TrickleReader {
+                input: &bytes,
+                error: None,
+            };
+            assert_eq!(run_parser_from_reader(reader).unwrap(), expected);
+            let mut scanner = Scanner::new_from_reader(&bytes[..]);
+            assert_eq!(scanner.next().unwrap().1, TokenType::StreamStart(encoding));
+        }
+    }
+
+    // Unpaired surrogates are invalid.
+    let Err(error) = run_parser_from_reader(&b"a\0:\0 \0\x00\xDC"[..]) else {
+        panic!()
+    };
+    assert_eq!(error.info(), "invalid UTF-16LE sequence in input");
+}
+
+#[test]
+fn test_bom() {
+    let expected = run_parser("a\n---\nb\n...\nc\n").unwrap();
+    assert_eq!(
+        run_parser("\u{FEFF}a\n\u{FEFF}---\nb\n...\n\u{FEFF}c\n").unwrap(),
+        expected
+    );
+    assert_eq!(
+        run_parser("\u{FEFF}a\n---\nb\n...\n\u{FEFF}# comment\nc\n").unwrap(),
+        expected
+    );
+
+    // A BOM is only allowed at the start of a document.
+    assert!(run_parser("a: \u{FEFF}b").is_err());
+    assert!(run_parser("a:\n\u{FEFF}b").is_err());
+}
+
+#[test]
+fn test_borrowed_scalars() {
+    let s = r#"
+plain: some words
+'single': "double"
+escaped: "a\tb"
+quote: 'it''s'
+folded: a
+  b
+literal: |
+  text
+"#;
+    let scalars: Vec<_> = run_parser(s)
+        .unwrap()
+        .into_iter()
+        .filter_map(|ev| match ev {
+            Event::Scalar(value, ..) => Some(value),
+            _ => None,
+        })
+        .collect();
+    let borrowed: Vec<_> = scalars
+        .iter()
+        .map(|value| (value.as_ref(), matches!(value, Cow::Borrowed(_))))
+        .collect();
+    assert_eq!(
+        borrowed,
+        [
+            ("plain", true),
+            ("some words", true),
+            ("single", true),
+            ("double", true),
+            ("escaped", true),
+            ("a\tb", false),
+            ("quote", true),
+            ("it's", false),
+            ("folded", true),
+            ("a b", false),
+            ("literal", true),
+            ("text\n", false),
+        ]
+    );
+
+    // Scalars read from a reader cannot be borrowed.
+    for ev in run_parser_from_reader(s.as_bytes()).unwrap() {
+        if let Event::Scalar(value, ..) = ev {
+            assert!(matches!(value, Cow::Owned(_)));
+        }
+    }
+}
+
+#[test]
+fn test_byte_offsets() {
+    let s = "é: 你好\n🦀: |\n  ünï\nkey: [ 'ö', \"ä\" ]\n";
+    for x in Parser::new_from_str(s) {
+        let (ev, mark) = x.unwrap();
+        if let Event::Scalar(value, TScalarStyle::Plain, ..) = ev {
+            assert!(s[mark.byte_offset()..].starts_with(&*value));
+        }
+    }
+    let marks: Vec<_> = Parser::new_from_str(s)
+        .map(|x| x.unwrap().1)
+        .map(|mark| (mark.index(), mark.byte_offset(), mark.line(), mark.col()))
+        .collect();
+    assert_eq!(
+        marks[5..11],
+        [
+            (6, 11, 2, 0),
+            (13, 21, 3, 2),
+            (17, 27, 4, 0),
+            (22, 32, 4, 5),
+            (24, 34, 4, 7),
+            (29, 40, 4, 12),
+        ],
+        "{marks:?}"
+    );
+
+    let Err(error) = run_parser("a: 你好\nb: ]") else {
+        panic!()
+    };
+    assert_eq!(error.marker().index(), 9);
+    assert_eq!(error.marker().byte_offset(), 13);
+    assert!(error.to_string().ends_with("at byte 13 line 2 column 4"));
+}
+
+#[test]
+fn test_spans() {
+    let s =
+        "a: 'quoted'\nb: |\n  block\n  text\nc:\n- [x, {y: z}]\n- [k: v]\n- ? \"d\"\ne: !t &e\n";
+    let events = run_parser_spanned(s, ParserOptions::default());
+    let text = |span: Span| &s[span.start.byte_offset()..span.end.byte_offset()];
+
+    let scalars: Vec<_> = events
+        .iter()
+        .filter(|(ev, _)| matches!(ev, Event::Scalar(..)))
+        .map(|(_, span)| text(*span))
+        .collect();
+    assert_eq!(
+        scalars,
+        [
+            "a",
+            "'quoted'",
+            "b",
+            "|\n  block\n  text",
+            "c",
+            "x",
+            "y",
+            "z",
+            "k",
+            "v",
+            "\"d\"",
+            "",
+            "e",
+            ""
+        ]
+    );
+
+    // The extent of a collection spans from its start event to its end event.
+    let mut starts = vec![];
+    let mut collections = vec![];
+    for (ev, span) in &events {
+        match ev {
+            Event::SequenceStart(..) | Event::MappingStart(..) => starts.push(span.start),
+            Event::SequenceEnd | Event::MappingEnd => {
+                let start = starts.pop().unwrap();
+                collections.push(&s[start.byte_offset()..span.end.byte_offset()]);
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(
+        collections,
+        [
+            "{y: z}",
+            "[x, {y: z}]",
+            "k: v",
+            "[k: v]",
+            "? \"d\"",
+            "- [x, {y: z}]\n- [k: v]\n- ? \"d\"",
+            &s[..s.len() - 1],
+        ]
+    );
+
+    // Empty scalars are zero-width, right after the last token.
+    let (_, span) = events
+        .iter()
+        .rev()
+        .find(|(ev, _)| matches!(ev, Event::Scalar(..)))
+        .unwrap();
+    assert!(span.is_empty());
+    assert_eq!(span.start.byte_offset(), s.len() - 1);
+
+    // Nor do they start before the previous event.
+    let events = run_parser_spanned("- :", ParserOptions::default());
+    let starts: Vec<_> = events.iter().map(|(_, span)| span.start.index()).collect();
+    assert_eq!(starts, [0, 0, 0, 2, 2, 3, 3, 3, 3, 3]);
+}
+
+#[test]
+fn test_anchor_names() {
+    let s = "a: &x 1\nb: !t &y [*x]\n--- &x\nc: *x\n";
+    let mut parser = Parser::new_from_str(s);
+    let mut names = vec![];
+    while let Some(x) = parser.next_event() {
+        let id = match x.unwrap().0 {
+            Event::Alias(id)
+            | Event::Scalar(_, _, id, _)
+            | Event::SequenceStart(id, ..)
+            | Event::MappingStart(id, ..) => id,
+            _ => continue,
+        };
+        if id > 0 {
+            names.push((id, parser.anchor_name(id).unwrap().to_owned()));
+        }
+    }
+    assert_eq!(
+        names,
+        [
+            (1, "x".to_owned()),
+            (2, "y".to_owned()),
+            (1, "x".to_owned()),
+            (3, "x".to_owned()),
+            (3, "x".to_owned()),
+        ]
+    );
+    assert_eq!(parser.anchor_name(0), None);
+    assert_eq!(parser.anchor_name(4), None);
+}
+
+#[test]
+fn test_document_markers_and_directives() {
+    let s = "%YAML 1.2\n%TAG !a! tag:a,2024:\n%TAG !b! tag:b,2024:\n--- !a!x\nfoo\n...\nbar\n";
+    assert_eq!(
+        run_parser(s).unwrap(),
+        [
+            Event::StreamStart,
+            Event::DocumentStart(
+                true,
+                Some((1, 2)),
+                vec![
+                    ("!a!".to_owned(), "tag:a,2024:".to_owned()),
+                    ("!b!".to_owned(), "tag:b,2024:".to_owned()),
+                ],
+                vec![],
+                YamlVersion::V1_2
+            ),
+            Event::Scalar(
+                "foo".into(),
+                TScalarStyle::Plain,
+                0,
+                Some(Tag {
+                    handle: "tag:a,2024:".to_owned(),
+                    suffix: "x".to_owned()
+                })
+            ),
+            Event::DocumentEnd(true),
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::Scalar("bar".into(), TScalarStyle::Plain, 0, None),
+            Event::DocumentEnd(false),
+            Event::StreamEnd,
+        ]
+    );
+
+    assert!(run_parser("%TAG !a! tag:a:\n%TAG !a! tag:b:\n--- foo").is_err());
+    assert!(run_parser("%YAML 1.2\n%YAML 1.2\n--- foo").is_err());
+}
+
+#[test]
+fn test_comments_kept() {
+    let s = "# head\na: b # trailing\n# before c\nc:\n  - d   #  d\n  # in seq\n  - [e, # flow\n    f]\n# end";
+    let events = run_parser_spanned(s, ParserOptions::default().keep_comments(true));
+    let comments: Vec<_> = events
+        .iter()
+        .filter_map(|(ev, span)| match ev {
+            Event::Comment(text, style) => Some((
+                text.clone(),
+                *style,
+                &s[span.start.byte_offset()..span.end.byte_offset()],
+            )),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        comments,
+        [
+            (" head".into(), CommentStyle::FullLine, "# head"),
+            (" trailing".into(), CommentStyle::Trailing, "# trailing"),
+            (" before c".into(), CommentStyle::FullLine, "# before c"),
+            ("  d".into(), CommentStyle::Trailing, "#  d"),
+            (" in seq".into(), CommentStyle::FullLine, "# in seq"),
+            (" flow".into(), CommentStyle::Trailing, "# flow"),
+            (" end".into(), CommentStyle::FullLine, "# end"),
+        ]
+    );
+    assert!(comments
+        .iter()
+        .all(|(text, ..)| matches!(text, Cow::Borrowed(_))));
+
+    // Comments are interleaved with the other events in source order.
+    let position = |needle: &Event| events.iter().position(|(ev, _)| ev == needle).unwrap();
+    let scalar = |v: &'static str| Event::Scalar(v.into(), TScalarStyle::Plain, 0, None);
+    let comment = |text: &'static str, style| Event::Comment(text.into(), style);
+    assert!(position(&comment(" head", CommentStyle::FullLine)) < position(&scalar("a")));
+    assert!(position(&scalar("e")) < position(&comment(" flow", CommentStyle::Trailing)));
+    assert!(position(&comment(" flow", CommentStyle::Trailing)) < position(&scalar("f")));
+    assert_eq!(
+        events[events.len() - 2].0,
+        comment(" end", CommentStyle::FullLine)
+    );
+
+    // Other events are unaffected.
+    let without_comments: Vec<_> = events
+        .into_iter()
+        .map(|(ev, _)| ev)
+        .filter(|ev| !matches!(ev, Event::Comment(..)))
+        .collect();
+    assert_eq!(without_comments, run_parser(s).unwrap());
+}
+
+#[test]
+fn test_block_scalar_headers() {
+    let header = |s| {
+        run_parser(s)
+            .unwrap()
+            .into_iter()
+            .find_map(|ev| match ev {
+                Event::Scalar(_, TScalarStyle::Literal(header), ..) => Some(('|', header)),
+                Event::Scalar(_, TScalarStyle::Folded(header), ..) => Some(('>', header)),
+                _ => None,
+            })
+            .unwrap()
+    };
+    let expected = |style, chomping, indentation_indicator, indent| {
+        (
+            style,
+            BlockScalarHeader {
+                chomping,
+                indentation_indicator,
+                indent,
+            },
+        )
+    };
+
+    assert_eq!(header("|\n x\n"), expected('|', Chomping::Clip, None, 1));
+    assert_eq!(
+        header("- >-\n  text\n"),
+        expected('>', Chomping::Strip, None, 2)
+    );
+    assert_eq!(
+        header("a: |+2\n    x\n"),
+        expected('|', Chomping::Keep, Some(2), 2)
+    );
+    assert_eq!(
+        header("a:\n  b: >1-\n    y\n"),
+        expected('>', Chomping::Strip, Some(1), 3)
+    );
+    assert_eq!(header("- |+\n"), expected('|', Chomping::Keep, None, 1));
+}
+
+#[test]
+fn test_omitted_values() {
+    let s = "a:\nb: ~\nc: &x\nd: !!str\n";
+    let values: Vec<_> = run_parser_spanned(s, ParserOptions::default())
+        .into_iter()
+        .filter_map(|(ev, span)| match ev {
+            Event::Scalar(value, _, _, _) => Some((value, span.is_empty())),
+            _ => None,
+        })
+        .skip(1)
+        .step_by(2)
+        .collect();
+    assert_eq!(
+        values,
+        [
+            (Cow::from("~"), true),
+            (Cow::from("~"), false),
+            (Cow::from("~"), true),
+            (Cow::from(""), true),
+        ]
+    );
+}
+
+#[test]
+fn test_recover_from_errors() {
+    let plain = |v| Event::Scalar(Cow::Borrowed(v), TScalarStyle::Plain, 0, None);
+    let recover = |s| {
+        let mut parser =
+            Parser::new_from_str(s).options(ParserOptions::default().recover_from_errors(true));
+        let events: Vec<_> = parser.by_ref().map(|ev| ev.unwrap().0).collect();
+        let errors: Vec<_> = parser
+            .errors()
+            .iter()
+            .map(|e| (e.kind().clone(), e.marker().line()))
+            .collect();
+        (events, errors)
+    };
+
+    let (events, errors) = recover("a: 1\nb: @x\nc: [d, @e,\n  f]\ng:\n- *unknown\n- h\n");
+    assert_eq!(
+        errors,
+        [
+            (ScanErrorKind::UnexpectedCharacter('@'), 2),
+            (ScanErrorKind::UnexpectedCharacter('@'), 3),
+            (ScanErrorKind::UnknownAnchor("unknown".into()), 6),
+        ]
+    );
+    assert_eq!(
+        events,
+        [
+            Event::StreamStart,
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::MappingStart(0, None, CollectionStyle::Block),
+            plain("a"),
+            plain("1"),
+            plain("b"),
+            Event::Invalid,
+            plain("c"),
+            Event::SequenceStart(0, None, CollectionStyle::Flow),
+            plain("d"),
+            Event::SequenceEnd,
+            plain("g"),
+            Event::SequenceStart(0, None, CollectionStyle::Indentless),
+            Event::Invalid,
+            plain("h"),
+            Event::SequenceEnd,
+            Event::MappingEnd,
+            Event::DocumentEnd(false),
+            Event::StreamEnd,
+        ]
+    );
+
+    // Collections left open at the end of the stream are closed.
+    let (events, errors) = recover("a: [b, {c: ");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        events,
+        [
+            Event::StreamStart,
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::MappingStart(0, None, CollectionStyle::Block),
+            plain("a"),
+            Event::SequenceStart(0, None, CollectionStyle::Flow),
+            plain("b"),
+            Event::MappingStart(0, None, CollectionStyle::Flow),
+            plain("c"),
+            Event::Invalid,
+            Event::MappingEnd,
+            Event::SequenceEnd,
+            Event::MappingEnd,
+            Event::DocumentEnd(false),
+            Event::StreamEnd,
+        ]
+    );
+
+    // Brackets in quoted scalars and comments do not close the flow collection being skipped.
+    for s in [
+        "a: [x, @, \"]\",\n  w]\nb: c",
+        "a: [x, @, '\\', ']',\n  w]\nb: c",
+        "a: [x, @, 'it''s ]',\n  w]\nb: c",
+        "a: [x, @, \"\\\"]\",\n  w]\nb: c",
+        "a: [x, @, # ]\n  w]\nb: c",
+    ] {
+        let (events, errors) = recover(s);
+        assert_eq!(
+            errors,
+            [(ScanErrorKind::UnexpectedCharacter('@'), 1)],
+            "{s:?}"
+        );
+        assert_eq!(
+            events[events.len() - 5..events.len() - 3],
+            [plain("b"), plain("c")]
+        );
+    }
+
+    // Aliases cannot refer to the anchor of a node that could not be parsed.
+    let (events, errors) = recover("a: &x :\n*x : b\n");
+    assert_eq!(
+        errors,
+        [
+            (ScanErrorKind::MappingValueNotAllowed, 1),
+            (ScanErrorKind::UnknownAnchor("x".into()), 2),
+        ]
+    );
+    assert!(!events.iter().any(|ev| matches!(ev, Event::Alias(_))));
+
+    // Skipping an unclosed flow collection stops at the next line of its block collection, so
+    // that the errors that follow are reported as well.
+    let (events, errors) = recover("a: [1, 2\nb: c\nd: }\ne: @\nf: g\n");
+    assert_eq!(
+        errors,
+        [
+            (ScanErrorKind::IllegalColonPlacement, 2),
+            (ScanErrorKind::ExpectedNodeContent, 3),
+            (ScanErrorKind::UnexpectedCharacter('@'), 4),
+        ]
+    );
+    assert_eq!(
+        events[events.len() - 9..events.len() - 2],
+        [
+            plain("d"),
+            Event::Invalid,
+            plain("e"),
+            Event::Invalid,
+            plain("f"),
+            plain("g"),
+            Event::MappingEnd,
+        ]
+    );
+
+    // Events generated when recovering are not placed before those emitted earlier.
+    for s in [
+        "a: 1 # c1\nb: [x, # c2\n  y\nc: 2 # c3\n",
+        "a:\n  - 1\n  -x: [\n  - 3\nb: ]\n",
+        "a:\n  b: [x, @,\n  c: d\n  e: ]\nf: g\n",
+    ] {
+        let options = ParserOptions::default()
+            .recover_from_errors(true)
+            .keep_comments(true);
+        let starts: Vec<_> = run_parser_spanned(s, options)
+            .iter()
+            .map(|(_, span)| span.start.index())
+            .collect();
+        assert!(starts.windows(2).all(|w| w[0] <= w[1]), "{s:?}: {starts:?}");
+    }
+
+    // Valid input is parsed as usual.
+    let s = "a: [b, {c: d}]\n---\n- e\n";
+    let (events, errors) = recover(s);
+    assert!(errors.is_empty());
+    assert_eq!(events, run_parser(s).unwrap());
+}
+
+#[test]
+fn test_error_context() {
+    let error = run_parser("a:\n  - [b, {c: d\n").unwrap_err();
+    assert_eq!(error.marker().line(), 3);
+    assert_eq!(
+        error.context(),
+        [
+            (ErrorContext::FlowMapping, Marker::new(11, 2, 8)),
+            (ErrorContext::FlowSequence, Marker::new(7, 2, 4)),
+            (ErrorContext::BlockSequence, Marker::new(5, 2, 2)),
+            (ErrorContext::BlockMapping, Marker::new(0, 1, 0)),
+            (ErrorContext::Document, Marker::new(0, 1, 0)),
+        ]
+    );
+
+    // Unterminated quoted scalars are reported at the end of the stream.
+    let error = run_parser("[a, \"b, c]\n").unwrap_err();
+    assert_eq!(error.kind(), &ScanErrorKind::UnterminatedQuotedScalar);
+    assert_eq!(error.marker().line(), 2);
+    assert_eq!(
+        error.context()[..2],
+        [
+            (ErrorContext::QuotedScalar, Marker::new(4, 1, 4)),
+            (ErrorContext::FlowSequence, Marker::new(0, 1, 0)),
+        ]
+    );
+}
+
+#[test]
+fn test_diagnostic() {
+    fn render(source: &str) -> String {
+        let error = run_parser(source).unwrap_err();
+        Diagnostic::new(&error, source).to_string()
+    }
+
+    assert_eq!(
+        render("a:\n\t[b: c, d: e]: f"),
+        "error: tabs disallowed within this context (block indentation)
+ --> 2:2
+  |
+2 | \t[b: c, d: e]: f
+  | \t^
+  = note: in the block mapping started at 1:1
+  = note: in the document started at 1:1"
+    );
+    // Errors at the end of the stream point past the last line.
+    assert_eq!(
+        render("a: [b\n"),
+        "error: while parsing a flow sequence, expected ',' or ']'
+ --> 2:1
+  |
+2 |
+  | ^
+  = note: in the flow sequence started at 1:4
+  = note: in the block mapping started at 1:1
+  = note: in the document started at 1:1"
+    );
+    let source = "a: b\r\nc: d: e";
+    let error = run_parser(source).unwrap_err();
+    let span = Span::new(Marker::new(9, 2, 3), Marker::new(13, 2, 7));
+    assert_eq!(
+        Diagnostic::new(&error, source)
+            .origin("input.yaml")
+            .span(span)
+            .to_string(),
+        "error: mapping values are not allowed in this context
+ --> input.yaml:2:4
+  |
+2 | c: d: e
+  |    ^^^^
+  = note: in the block mapping started at input.yaml:1:1
+  = note: in the document started at input.yaml:1:1"
+    );
+    let error = ScanError::new_str(Marker::new(10, 2, 4), "invalid value")
+        .with_context(ErrorContext::Document, Marker::new(0, 1, 0));
+    assert_eq!(
+        Diagnostic::new(&error, source).colors(true).to_string(),
+        "\x1B[1;31merror\x1B[0m\x1B[1m: invalid value\x1B[0m
+ \x1B[1;34m-->\x1B[0m 2:5
+  \x1B[1;34m|\x1B[0m
+\x1B[1;34m2 |\x1B[0m c: d: e
+  \x1B[1;34m|\x1B[0m     \x1B[1;31m^\x1B[0m
+  \x1B[1;34m=\x1B[0m \x1B[1mnote\x1B[0m: in the document started at 1:1"
+    );
+    // Line separators break lines in YAML 1.1 only.
+    let source = "a: b\u{2028}c: d: e\n";
+    for (version, expected) in [
+        (YamlVersion::V1_1, "2 | c: d: e\n  |     ^"),
+        (YamlVersion::V1_2, "1 | a: b\u{2028}c: d: e\n  |       ^"),
+    ] {
+        let error = Parser::new_from_str(source)
+            .options(ParserOptions::default().yaml_version(version))
+            .find_map(Result::err)
+            .unwrap();
+        let rendered = Diagnostic::new(&error, source).to_string();
+        assert!(rendered.contains(expected), "{rendered}");
+    }
+}
+
+#[test]
+fn test_warnings() {
+    let s = "%FOO bar baz
+%YAML 1.3
+---
+a: &x 1
+b: &x 2
+c: *x
+---
+d: &x 3
+";
+    let mut parser = Parser::new_from_str(s);
+    let mut events = Vec::new();
+    for event in parser.by_ref() {
+        events.push(event.unwrap().0);
+    }
+    assert_eq!(
+        events[1],
+        Event::DocumentStart(true, Some((1, 3)), vec![], vec![], YamlVersion::V1_2)
+    );
+    assert_eq!(events[8], Event::Alias(2));
+    let warnings: Vec<_> = parser
+        .warnings()
+        .iter()
+        .map(|w| (w.kind().clone(), w.marker().line(), w.marker().col()))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (WarningKind::UnknownDirective("FOO".into()), 1, 0),
+            (WarningKind::HigherMinorVersion(1, 3), 2, 0),
+            (WarningKind::RedefinedAnchor("x".into()), 5, 3),
+        ]
+    );
+    assert_eq!(
+        parser.warnings()[0].to_string(),
+        "found unknown directive '%FOO' at byte 0 line 1 column 1"
+    );
+}
+
+#[test]
+fn test_expected_tokens() {
+    let error = run_parser("[a, b}").unwrap_err();
+    assert_eq!(error.found(), Some(TokenKind::FlowMappingEnd));
+    assert_eq!(
+        error.expected(),
+        [TokenKind::FlowEntry, TokenKind::FlowSequenceEnd]
+    );
+
+    let error = run_parser("a: b\n- c").unwrap_err();
+    assert_eq!(error.kind(), &ScanErrorKind::ExpectedBlockMappingKey);
+    assert_eq!(error.found(), Some(TokenKind::BlockEntry));
+    assert_eq!(
+        error.expected(),
+        [TokenKind::Key, TokenKind::Value, TokenKind::BlockEnd]
+    );
+
+    let error = run_parser("{a: ]").unwrap_err();
+    assert_eq!(error.kind(), &ScanErrorKind::ExpectedNodeContent);
+    assert_eq!(error.found(), Some(TokenKind::FlowSequenceEnd));
+    assert!(!error.expected().contains(&TokenKind::BlockMappingStart));
+    assert!(error.expected().contains(&TokenKind::Scalar));
+
+    // Errors from the scanner are not about an unexpected token.
+    let error = run_parser("a: 'b").unwrap_err();
+    assert_eq!(error.found(), None);
+    assert!(error.expected().is_empty());
+}
+
+#[test]
+fn test_parser_options() {
+    let s = "%TAG !t! tag:t,2024:\n--- !t!a\nb: # c\n--- !t!d\n@e: f\n";
+    let options = ParserOptions::default()
+        .keep_tags(true)
+        .keep_comments(true)
+        .recover_from_errors(true)
+        .limits(Limits::default().max_documents(2));
+    let count = |parser: &mut dyn Iterator<Item = Result<(Event, Marker), ScanError>>| {
+        let events: Vec<_> = parser.map(|ev| ev.unwrap().0).collect();
+        let comments = events
+            .iter()
+            .filter(|ev| matches!(ev, Event::Comment(..)))
+            .count();
+        let invalid = events.iter().filter(|ev| **ev == Event::Invalid).count();
+        (comments, invalid)
+    };
+
+    // Options apply the same whatever the input.
+    let mut parser = Parser::new_from_str(s).options(options);
+    assert_eq!(count(&mut parser), (1, 1));
+    assert_eq!(parser.errors().len(), 1);
+    let mut parser = Parser::new_from_reader(s.as_bytes()).options(options);
+    assert_eq!(count(&mut parser), (1, 1));
+    let mut parser = Parser::new(StrInput::new(s)).options(options);
+    assert_eq!(count(&mut parser), (1, 1));
+
+    // Setting options replaces those set before.
+    let mut parser = Parser::new_from_str(s)
+        .options(options)
+        .options(options.keep_comments(false));
+    assert_eq!(count(&mut parser), (0, 1));
+    let error = Parser::new_from_str(s)
+        .options(options.keep_tags(false).recover_from_errors(false))
+        .find_map(Result::err)
+        .unwrap();
+    assert_eq!(
+        error.kind(),
+        &ScanErrorKind::UndeclaredTagHandle("!t!".into())
+    );
+    let error = Parser::new_from_str(s)
+        .options(options.limits(Limits::default().max_documents(1)))
+        .find_map(Result::err)
+        .unwrap();
+    assert_eq!(error.kind(), &ScanErrorKind::DocumentLimitExceeded);
+
+    // The deprecated setter still sets its option.
+    #[allow(deprecated)]
+    let parser = Parser::new_from_str("%TAG !t! tag:t,2024:\n--- !t!a\n--- !t!b\n").keep_tags(true);
+    assert!(parser.map(Result::unwrap).count() > 0);
+
+    // The scanner takes into account the options which apply to it.
+    let mut scanner = Scanner::new(StrInput::new(s)).options(options);
+    assert!(scanner.by_ref().count() > 0);
+    assert!(scanner
+        .pop_comment_before(Marker::new(usize::MAX, 0, 0))
+        .is_some());
+}
+
+#[test]
+fn test_limits() {
+    fn limit_error(source: &str, limits: Limits) -> Option<ScanErrorKind> {
+        Parser::new_from_str(source)
+            .options(ParserOptions::default().limits(limits))
+            .find_map(Result::err)
+            .map(|e| e.kind().clone())
+    }
+
+    let depth = "a:\n  - [b, {c: d}]";
+    assert_eq!(limit_error(depth, Limits::default().max_depth(4)), None);
+    assert_eq!(
+        limit_error(depth, Limits::default().max_depth(3)),
+        Some(ScanErrorKind::DepthLimitExceeded)
+    );
+
+    for scalar in ["abc def", "'abc def'", "\"abc def\"", "|-\n  abc\n  def\n"] {
+        let source = format!("a: {scalar}");
+        assert_eq!(
+            limit_error(&source, Limits::default().max_scalar_bytes(7)),
+            None
+        );
+        assert_eq!(
+            limit_error(&source, Limits::default().max_scalar_bytes(6)),
+            Some(ScanErrorKind::ScalarLimitExceeded)
+        );
+    }
+
+    // The limit is enforced as the scalar is read, including the line breaks and whitespace held
+    // until the next content, and also bounds comments, anchors and tags.
+    for (prefix, filler) in [
+        (&b"a: "[..], b'x'),
+        (b"a: b", b' '),
+        (b"a: b", b'\n'),
+        (b"a: '", b'x'),
+        (b"a: 'b", b' '),
+        (b"a: 'b", b'\n'),
+        (b"a: \"", b'x'),
+        (b"a: |\n  ", b'x'),
+        (b"a: |\n", b'\n'),
+        (b"a: >\n  b\n", b'\n'),
+        (b"&", b'x'),
+        (b"!", b'x'),
+        (b"!<", b'x'),
+        (b"!!", b'x'),
+        (b"%TAG !a! ", b'x'),
+        (b"%", b'x'),
+        (b"#", b'x'),
+    ] {
+        let input = prefix.chain(std::io::repeat(filler));
+        let error = Parser::new_from_reader(input)
+            .options(
+                ParserOptions::default()
+                    .keep_comments(true)
+                    .limits(Limits::default().max_scalar_bytes(1000)),
+            )
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(
+            error.kind(),
+            &ScanErrorKind::ScalarLimitExceeded,
+            "{prefix:?}"
+        );
+    }
+
+    // StreamStart, DocumentStart, SequenceStart, 2 scalars, SequenceEnd, DocumentEnd, StreamEnd
+    let events = "- a\n- b";
+    assert_eq!(limit_error(events, Limits::default().max_events(8)), None);
+    assert_eq!(
+        limit_error(events, Limits::default().max_events(7)),
+        Some(ScanErrorKind::EventLimitExceeded)
+    );
+
+    let documents = "a\n---\nb\n---\nc";
+    assert_eq!(
+        limit_error(documents, Limits::default().max_documents(3)),
+        None
+    );
+    assert_eq!(
+        limit_error(documents, Limits::default().max_documents(2)),
+        Some(ScanErrorKind::DocumentLimitExceeded)
+    );
+
+    let anchors = "- &a 1\n- &b 2\n- *a\n- *b\n- *a";
+    assert_eq!(limit_error(anchors, Limits::default().max_anchors(2)), None);
+    assert_eq!(
+        limit_error(anchors, Limits::default().max_anchors(1)),
+        Some(ScanErrorKind::AnchorLimitExceeded)
+    );
+    // Anchors of all documents count toward the limit.
+    assert_eq!(
+        limit_error("&a x\n---\n&a y", Limits::default().max_anchors(1)),
+        Some(ScanErrorKind::AnchorLimitExceeded)
+    );
+    assert_eq!(limit_error(anchors, Limits::default().max_aliases(3)), None);
+    assert_eq!(
+        limit_error(anchors, Limits::default().max_aliases(2)),
+        Some(ScanErrorKind::AliasLimitExceeded)
+    );
+
+    // Limits are not recovered from.
+    let mut parser = Parser::new_from_str(anchors).options(
+        ParserOptions::default()
+            .recover_from_errors(true)
+            .limits(Limits::default().max_aliases(2)),
+    );
+    let error = parser.find_map(Result::err).unwrap();
+    assert_eq!(error.kind(), &ScanErrorKind::AliasLimitExceeded);
+    assert!(parser.errors().is_empty());
+
+    // Events generated when recovering from errors count toward the limits as well.
+    let recover = |limits| {
+        Parser::new_from_str("a: [b, ")
+            .options(
+                ParserOptions::default()
+                    .recover_from_errors(true)
+                    .limits(limits),
+            )
+            .find_map(Result::err)
+            .map(|e| e.kind().clone())
+    };
+    assert_eq!(recover(Limits::default().max_events(9)), None);
+    assert_eq!(
+        recover(Limits::default().max_events(8)),
+        Some(ScanErrorKind::EventLimitExceeded)
+    );
+}
+
+#[test]
+fn test_alias_expansion_limits() {
+    fn limit_error(source: &str, limits: Limits) -> Option<ScanErrorKind> {
+        Parser::new_from_str(source)
+            .options(ParserOptions::default().limits(limits))
+            .find_map(Result::err)
+            .map(|e| e.kind().clone())
+    }
+
+    let laughs = "a: &a [x, x, x, x, x, x, x, x, x]
+b: &b [*a, *a, *a, *a, *a, *a, *a, *a, *a]
+c: &c [*b, *b, *b, *b, *b, *b, *b, *b, *b]
+d: &d [*c, *c, *c, *c, *c, *c, *c, *c, *c]
+e: &e [*d, *d, *d, *d, *d, *d, *d, *d, *d]
+f: &f [*e, *e, *e, *e, *e, *e, *e, *e, *e]
+g: &g [*f, *f, *f, *f, *f, *f, *f, *f, *f]
+h: &h [*g, *g, *g, *g, *g, *g, *g, *g, *g]
+i: &i [*h, *h, *h, *h, *h, *h, *h, *h, *h]
+";
+    assert_eq!(limit_error(laughs, Limits::default()), None);
+    assert_eq!(
+        limit_error(laughs, Limits::default().max_expanded_nodes(1_000_000)),
+        Some(ScanErrorKind::ExpandedNodeLimitExceeded)
+    );
+    assert_eq!(
+        limit_error(laughs, Limits::default().max_expansion_ratio(100)),
+        Some(ScanErrorKind::ExpansionRatioExceeded)
+    );
+
+    // The mapping (1), `a` and `b` (2), the sequence (1 + 3) and the alias (4).
+    let reuse = "a: &a [1, 2, 3]\nb: *a";
+    assert_eq!(
+        limit_error(reuse, Limits::default().max_expanded_nodes(11)),
+        None
+    );
+    assert_eq!(
+        limit_error(reuse, Limits::default().max_expanded_nodes(10)),
+        Some(ScanErrorKind::ExpandedNodeLimitExceeded)
+    );
+    // 8 nodes in the input: the alias expands to 4.
+    assert_eq!(
+        limit_error(reuse, Limits::default().max_expansion_ratio(2)),
+        None
+    );
+    assert_eq!(
+        limit_error(reuse, Limits::default().max_expansion_ratio(1)),
+        Some(ScanErrorKind::ExpansionRatioExceeded)
+    );
+
+    // Recursive aliases count as a single node.
+    assert_eq!(
+        limit_error("&a [*a]", Limits::default().max_expanded_nodes(2)),
+        None
+    );
+}
+
+#[test]
+fn test_yaml_1_1() {
+    let parse = |s, version| -> Result<Vec<Event>, ScanError> {
+        Parser::new_from_str(s)
+            .options(ParserOptions::default().yaml_version(version))
+            .map(|ev| ev.map(|ev| ev.0))
+            .collect()
+    };
+    let scalars = |events: &[Event]| -> Vec<String> {
+        events
+            .iter()
+            .filter_map(|ev| match ev {
+                Event::Scalar(value, ..) => Some(value.to_string()),
+                _ => None,
+            })
+            .collect()
+    };
+    let versions = |events: &[Event]| -> Vec<YamlVersion> {
+        events
+            .iter()
+            .filter_map(|ev| match ev {
+                Event::DocumentStart(.., version) => Some(*version),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // NEL, LS and PS are only line breaks in YAML 1.1, where LS and PS are not folded.
+    let s = "a: b\u{85}c: |\n d\u{2028} e\u{85} f\n";
+    let error = parse(s, YamlVersion::V1_2).unwrap_err();
+    assert_eq!(error.kind(), &ScanErrorKind::MappingValueNotAllowed);
+    let events = parse(s, YamlVersion::V1_1).unwrap();
+    assert_eq!(scalars(&events), ["a", "b", "c", "d\u{2028}e\nf\n"]);
+    assert_eq!(versions(&events), [YamlVersion::V1_1]);
+    let s = "- >\n a\u{85} b\u{2029} c\n- d\u{85} e\n";
+    let events = parse(s, YamlVersion::V1_1).unwrap();
+    assert_eq!(scalars(&events), ["a b\u{2029}c\n", "d e"]);
+
+    // `%YAML` directives select the version of the document they precede.
+    let s = "%YAML 1.1\n--- \"a\u{85} b\"\n...\n\"c\u{85} d\"\n...\n%YAML 1.2\n--- e\n";
+    let events = parse(s, YamlVersion::V1_2).unwrap();
+    assert_eq!(scalars(&events), ["a b", "c\u{85} d", "e"]);
+    assert_eq!(
+        versions(&events),
+        [YamlVersion::V1_1, YamlVersion::V1_2, YamlVersion::V1_2]
+    );
+    let events = parse(s, YamlVersion::V1_1).unwrap();
+    assert_eq!(scalars(&events), ["a b", "c d", "e"]);
+    assert_eq!(
+        versions(&events),
+        [YamlVersion::V1_1, YamlVersion::V1_1, YamlVersion::V1_2]
+    );
+
+    // YAML 1.1 has no `\/` escape.
+    let s = "\"a\\/b\"";
+    assert_eq!(scalars(&parse(s, YamlVersion::V1_2).unwrap()), ["a/b"]);
+    let error = parse(s, YamlVersion::V1_1).unwrap_err();
+    assert_eq!(error.kind(), &ScanErrorKind::UnknownEscape('/'));
+}
+
+#[test]
+fn test_lenient() {
+    let s = "a:\n\tb: \"c\"#d\n\td:\n\t- e\n`f`: g\n\th\n";
+    let error = run_parser(s).unwrap_err();
+    assert_eq!(error.kind(), &ScanErrorKind::TabsInBlockIndentation);
+
+    let mut parser = Parser::new_from_str(s).options(ParserOptions::default().lenient(true));
+    let events: Vec<_> = parser.by_ref().map(|ev| ev.unwrap().0).collect();
+    let scalars: Vec<_> = events
+        .iter()
+        .filter_map(|ev| match ev {
+            Event::Scalar(value, ..) => Some(value.as_ref()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(scalars, ["a", "b", "c", "d", "e", "`f`", "g h"]);
+    let warnings: Vec<_> = parser
+        .warnings()
+        .iter()
+        .map(|w| (w.kind().clone(), w.marker().line(), w.marker().col()))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (WarningKind::TabIndentation, 2, 0),
+            (WarningKind::CommentWithoutWhitespace, 2, 7),
+            (WarningKind::TabIndentation, 3, 0),
+            (WarningKind::TabIndentation, 4, 0),
+            (WarningKind::ReservedIndicator('`'), 5, 0),
+            (WarningKind::TabIndentation, 6, 0),
+        ]
+    );
+}
@@ -1,10 +1,13 @@
-use saphyr_parser::{Event, Parser, ScanError, TScalarStyle};
+use saphyr_parser::{
+    CollectionStyle::{self, Block, Flow, FlowPair, Indentless},
+    Event, Parser, ScanError, TScalarStyle, YamlVersion,
+};
 
 /// Run the parser through the string.
 ///
 /// # Returns
 /// This functions returns the events if parsing succeeds, the error the parser returned otherwise.
-fn run_parser(input: &str) -> Result<Vec<Event>, ScanError> {
+fn run_parser(input: &str) -> Result<Vec<Event<'_>>, ScanError> {
     let mut events = vec![];
     for x in Parser::new_from_str(input) {
         events.push(x?.0);
@@ -32,23 +35,34 @@ fn test_issue1() {
   - 42
 ";
 
-    let expected = [
-        Event::StreamStart,
-        Event::DocumentStart,
-        Event::SequenceStart(0, None),
-        Event::MappingStart(0, None),
-        Event::Scalar("a".to_string(), TScalarStyle::Plain, 0, None),
-        Event::SequenceStart(0, None),
-        Event::Scalar("42".to_string(), TScalarStyle::Plain, 0, None),
-        Event::SequenceEnd,
-        Event::MappingEnd,
-        Event::SequenceEnd,
-        Event::DocumentEnd,
-        Event::StreamEnd,
-    ];
-    assert_eq!(run_parser(reference).unwrap(), expected);
-    assert_eq!(run_parser("[{a: [42]}]").unwrap(), expected);
-    assert_eq!(run_parser("[a: [42]]").unwrap(), expected);
+    let expected = |styles: [CollectionStyle; 3]| {
+        [
+            Event::StreamStart,
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::SequenceStart(0, None, styles[0]),
+            Event::MappingStart(0, None, styles[1]),
+            Event::Scalar("a".into(), TScalarStyle::Plain, 0, None),
+            Event::SequenceStart(0, None, styles[2]),
+            Event::Scalar("42".into(), TScalarStyle::Plain, 0, None),
+            Event::SequenceEnd,
+            Event::MappingEnd,
+            Event::SequenceEnd,
+            Event::DocumentEnd(false),
+            Event::StreamEnd,
+        ]
+    };
+    assert_eq!(
+        run_parser(reference).unwrap(),
+        expected([Block, Block, Indentless])
+    );
+    assert_eq!(
+        run_parser("[{a: [42]}]").unwrap(),
+        expected([Flow, Flow, Flow])
+    );
+    assert_eq!(
+        run_parser("[a: [42]]").unwrap(),
+        expected([Flow, FlowPair, Flow])
+    );
 
     // Other test cases derived from the bug
 
@@ -57,19 +71,19 @@ fn test_issue1() {
         run_parser("[foo: [bar]]: baz").unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::MappingStart(0, None),
-            Event::SequenceStart(0, None),
-            Event::MappingStart(0, None),
-            Event::Scalar("foo".to_string(), TScalarStyle::Plain, 0, None),
-            Event::SequenceStart(0, None),
-            Event::Scalar("bar".to_string(), TScalarStyle::Plain, 0, None),
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::MappingStart(0, None, Block),
+            Event::SequenceStart(0, None, Flow),
+            Event::MappingStart(0, None, FlowPair),
+            Event::Scalar("foo".into(), TScalarStyle::Plain, 0, None),
+            Event::SequenceStart(0, None, Flow),
+            Event::Scalar("bar".into(), TScalarStyle::Plain, 0, None),
             Event::SequenceEnd,
             Event::MappingEnd,
             Event::SequenceEnd,
-            Event::Scalar("baz".to_string(), TScalarStyle::Plain, 0, None),
+            Event::Scalar("baz".into(), TScalarStyle::Plain, 0, None),
             Event::MappingEnd,
-            Event::DocumentEnd,
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -79,14 +93,14 @@ fn test_issue1() {
         run_parser("[:]").unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::SequenceStart(0, None),
-            Event::MappingStart(0, None),
-            Event::Scalar("~".to_string(), TScalarStyle::Plain, 0, None),
-            Event::Scalar("~".to_string(), TScalarStyle::Plain, 0, None),
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::SequenceStart(0, None, Flow),
+            Event::MappingStart(0, None, FlowPair),
+            Event::Scalar("~".into(), TScalarStyle::Plain, 0, None),
+            Event::Scalar("~".into(), TScalarStyle::Plain, 0, None),
             Event::MappingEnd,
             Event::SequenceEnd,
-            Event::DocumentEnd,
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -96,19 +110,19 @@ fn test_issue1() {
         run_parser("[: [:]]").unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::SequenceStart(0, None),
-            Event::MappingStart(0, None),
-            Event::Scalar("~".to_string(), TScalarStyle::Plain, 0, None),
-            Event::SequenceStart(0, None),
-            Event::MappingStart(0, None),
-            Event::Scalar("~".to_string(), TScalarStyle::Plain, 0, None),
-            Event::Scalar("~".to_string(), TScalarStyle::Plain, 0, None),
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::SequenceStart(0, None, Flow),
+            Event::MappingStart(0, None, FlowPair),
+            Event::Scalar("~".into(), TScalarStyle::Plain, 0, None),
+            Event::SequenceStart(0, None, Flow),
+            Event::MappingStart(0, None, FlowPair),
+            Event::Scalar("~".into(), TScalarStyle::Plain, 0, None),
+            Event::Scalar("~".into(), TScalarStyle::Plain, 0, None),
             Event::MappingEnd,
             Event::SequenceEnd,
             Event::MappingEnd,
             Event::SequenceEnd,
-            Event::DocumentEnd,
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -123,23 +137,23 @@ fn test_issue1() {
         // third nested sequences, but not the second.
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::SequenceStart(0, None),
-            Event::MappingStart(0, None),
-            Event::Scalar("a".to_string(), TScalarStyle::Plain, 0, None),
-            Event::SequenceStart(0, None),
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::SequenceStart(0, None, Flow),
+            Event::MappingStart(0, None, FlowPair),
+            Event::Scalar("a".into(), TScalarStyle::Plain, 0, None),
+            Event::SequenceStart(0, None, Flow),
             // No `MappingStart` here.
-            Event::SequenceStart(0, None),
-            Event::MappingStart(0, None),
-            Event::Scalar("b".to_string(), TScalarStyle::Plain, 0, None),
-            Event::Scalar("~".to_string(), TScalarStyle::Plain, 0, None),
+            Event::SequenceStart(0, None, Flow),
+            Event::MappingStart(0, None, FlowPair),
+            Event::Scalar("b".into(), TScalarStyle::Plain, 0, None),
+            Event::Scalar("~".into(), TScalarStyle::Plain, 0, None),
             Event::MappingEnd,
             Event::SequenceEnd,
             // No `MappingEnd` here.
             Event::SequenceEnd,
             Event::MappingEnd,
             Event::SequenceEnd,
-            Event::DocumentEnd,
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
@@ -153,15 +167,15 @@ fn test_issue1() {
         run_parser(r#"["a":[]]"#).unwrap(),
         [
             Event::StreamStart,
-            Event::DocumentStart,
-            Event::SequenceStart(0, None),
-            Event::MappingStart(0, None),
-            Event::Scalar("a".to_string(), TScalarStyle::DoubleQuoted, 0, None),
-            Event::SequenceStart(0, None),
+            Event::DocumentStart(false, None, vec![], vec![], YamlVersion::V1_2),
+            Event::SequenceStart(0, None, Flow),
+            Event::MappingStart(0, None, FlowPair),
+            Event::Scalar("a".into(), TScalarStyle::DoubleQuoted, 0, None),
+            Event::SequenceStart(0, None, Flow),
             Event::SequenceEnd,
             Event::MappingEnd,
             Event::SequenceEnd,
-            Event::DocumentEnd,
+            Event::DocumentEnd(false),
             Event::StreamEnd,
         ]
     );
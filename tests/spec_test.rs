@@ -23,11 +23,11 @@ struct YamlChecker {
     pub evs: Vec<TestEvent>,
 }
 
-impl EventReceiver for YamlChecker {
+impl EventReceiver<'_> for YamlChecker {
     fn on_event(&mut self, ev: Event) {
         let tev = match ev {
-            Event::DocumentStart => TestEvent::OnDocumentStart,
-            Event::DocumentEnd => TestEvent::OnDocumentEnd,
+            Event::DocumentStart(..) => TestEvent::OnDocumentStart,
+            Event::DocumentEnd(_) => TestEvent::OnDocumentEnd,
             Event::SequenceStart(..) => TestEvent::OnSequenceStart,
             Event::SequenceEnd => TestEvent::OnSequenceEnd,
             Event::MappingStart(..) => TestEvent::OnMapStart,
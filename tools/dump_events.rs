@@ -1,6 +1,6 @@
 use std::env;
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::Read;
 
 use saphyr_parser::{
     parser::{MarkedEventReceiver, Parser},
@@ -9,20 +9,20 @@ use saphyr_parser::{
 };
 
 #[derive(Debug)]
-struct EventSink {
-    events: Vec<(Event, Marker)>,
+struct EventSink<'input> {
+    events: Vec<(Event<'input>, Marker)>,
 }
 
-impl MarkedEventReceiver for EventSink {
-    fn on_event(&mut self, ev: Event, mark: Marker) {
+impl<'input> MarkedEventReceiver<'input> for EventSink<'input> {
+    fn on_event(&mut self, ev: Event<'input>, mark: Marker) {
         eprintln!("      \x1B[;34m\u{21B3} {:?}\x1B[;m", &ev);
         self.events.push((ev, mark));
     }
 }
 
-fn str_to_events(yaml: &str) -> Vec<(Event, Marker)> {
+fn reader_to_events<R: Read>(reader: R) -> Vec<(Event<'static>, Marker)> {
     let mut sink = EventSink { events: Vec::new() };
-    let mut parser = Parser::new_from_str(yaml);
+    let mut parser = Parser::new_from_reader(reader);
     // Load events using our sink as the receiver.
     parser.load(&mut sink, true).unwrap();
     sink.events
@@ -30,10 +30,8 @@ fn str_to_events(yaml: &str) -> Vec<(Event, Marker)> {
 
 fn main() {
     let args: Vec<_> = env::args().collect();
-    let mut f = File::open(&args[1]).unwrap();
-    let mut s = String::new();
-    f.read_to_string(&mut s).unwrap();
+    let f = File::open(&args[1]).unwrap();
 
-    // dbg!(str_to_events(&s));
-    str_to_events(&s);
+    // dbg!(reader_to_events(f));
+    reader_to_events(f);
 }
@@ -0,0 +1,92 @@
+use std::{env, error::Error, fs, path::Path};
+
+use saphyr_parser::{Event, Parser};
+
+/// The fuzz targets, each of which has its own corpus directory.
+const TARGETS: [&str; 2] = ["parser", "scanner"];
+
+/// Extract the inputs of the tests in `source`, a file of the YAML test suite.
+///
+/// A file is a sequence of tests, each a mapping whose `yaml` key holds the input, written with
+/// visual markers for whitespace.
+fn extract_inputs(source: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut inputs = vec![];
+    let mut depth = 0;
+    // The number of nodes so far in the mapping of the current test.
+    let mut nodes = 0;
+    let mut is_input = false;
+    for event in Parser::new_from_str(source) {
+        match event?.0 {
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                if depth == 2 {
+                    nodes += 1;
+                }
+                depth += 1;
+                if depth == 2 {
+                    nodes = 0;
+                }
+            }
+            Event::SequenceEnd | Event::MappingEnd => depth -= 1,
+            Event::Scalar(value, ..) if depth == 2 => {
+                if nodes % 2 == 0 {
+                    is_input = value == "yaml";
+                } else if is_input {
+                    inputs.push(visual_to_raw(&value));
+                }
+                nodes += 1;
+            }
+            Event::Alias(..) if depth == 2 => nodes += 1,
+            _ => {}
+        }
+    }
+    Ok(inputs)
+}
+
+/// Replace the visual markers of the YAML test suite with the characters they stand for.
+fn visual_to_raw(yaml: &str) -> String {
+    let mut yaml = yaml.to_owned();
+    for (pat, replacement) in [
+        ("␣", " "),
+        ("»", "\t"),
+        ("—", ""), // Tab line continuation ——»
+        ("←", "\r"),
+        ("⇔", "\u{FEFF}"),
+        ("↵", ""), // Trailing newline marker
+        ("∎\n", ""),
+    ] {
+        yaml = yaml.replace(pat, replacement);
+    }
+    yaml
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<_> = env::args().collect();
+    let suite = Path::new(
+        args.get(1)
+            .map_or("tests/yaml-test-suite/src", String::as_str),
+    );
+    let corpus = Path::new(args.get(2).map_or("fuzz/corpus", String::as_str));
+
+    for target in TARGETS {
+        fs::create_dir_all(corpus.join(target))?;
+    }
+    let mut count = 0;
+    for entry in fs::read_dir(suite)? {
+        let path = entry?.path();
+        let name = path
+            .file_stem()
+            .ok_or("unexpected file name")?
+            .to_string_lossy();
+        for (idx, input) in extract_inputs(&fs::read_to_string(&path)?)?
+            .iter()
+            .enumerate()
+        {
+            for target in TARGETS {
+                fs::write(corpus.join(target).join(format!("{name}-{idx:02}")), input)?;
+            }
+            count += 1;
+        }
+    }
+    println!("Seeded {count} inputs into {}", corpus.display());
+    Ok(())
+}